@@ -0,0 +1,355 @@
+use rocket::{Request, Route, State};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::{self, Responder, status};
+use rocket::serde::{Deserialize, Serialize, json::Json};
+
+use rusqlite::{Connection, ErrorCode, OptionalExtension, Row, params};
+
+use chrono::NaiveDate;
+
+use crate::PersistentData;
+use crate::question_type::{self, QuestionType};
+use crate::poll::{self, Prompt};
+
+/// Bearer token the admin API expects, None disables the admin API.
+pub struct AdminToken(pub Option<String>);
+
+/// Request guard that only lets requests carrying "Authorization: Bearer <admin_token>" through.
+pub struct Admin;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let expected_token = match request.rocket().state::<AdminToken>() {
+            Some(AdminToken(Some(expected_token))) => expected_token,
+            _ => return Outcome::Error((Status::Unauthorized, ()))
+        };
+
+        let given_token = request.headers().get_one("Authorization").and_then(|header| header.strip_prefix("Bearer "));
+
+        match given_token {
+            Some(given_token) if tokens_match(given_token, expected_token) => Outcome::Success(Admin),
+            _ => Outcome::Error((Status::Unauthorized, ()))
+        }
+    }
+}
+
+/// Compare two tokens without bailing out on the first differing byte, so response times don't leak the token.
+fn tokens_match(given_token: &str, expected_token: &str) -> bool {
+    given_token.len() == expected_token.len()
+        && given_token.bytes().zip(expected_token.bytes()).fold(0, |difference, (a, b)| difference | (a ^ b)) == 0
+}
+
+/// Struct that represents a prompt as stored in the prompts and prompt_options tables.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PromptRecord {
+    id: i64,                                // Id of the prompt.
+    options: Vec<String>,                   // Text of every option, in order.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    red_prompt: Option<String>,             // Prompt shown on the red side, two-option prompts only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blue_prompt: Option<String>,            // Prompt shown on the blue side, two-option prompts only.
+    question_type: QuestionType,            // QuestionType of the prompt.
+    scheduled_date: Option<NaiveDate>       // Date the prompt runs on, None if it's not scheduled yet.
+}
+
+impl PromptRecord {
+    /// Build a PromptRecord from a row selecting id, question_type, scheduled_date, along with the prompt's options.
+    fn from_row(db_conn: &Connection, row: &Row) -> rusqlite::Result<Self> {
+        let id = row.get(0)?;
+        let options = poll::options_of_prompt(db_conn, id)?;
+
+        let (red_prompt, blue_prompt) = match options.as_slice() {
+            [red_prompt, blue_prompt] => (Some(red_prompt.clone()), Some(blue_prompt.clone())),
+            _ => (None, None)
+        };
+
+        Ok(PromptRecord {
+            id,
+            options,
+            red_prompt,
+            blue_prompt,
+            question_type: row.get(1)?,
+            scheduled_date: row.get(2)?
+        })
+    }
+}
+
+/// Struct that represents the JSON body of a create or update prompt request, which has either red_prompt and blue_prompt or options.
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde", deny_unknown_fields)]
+pub struct PromptInput {
+    #[serde(default)]
+    red_prompt: Option<String>,
+    #[serde(default)]
+    blue_prompt: Option<String>,
+    #[serde(default)]
+    options: Option<Vec<String>>,
+    question_type: QuestionType,
+    #[serde(default)]
+    scheduled_date: Option<NaiveDate>
+}
+
+impl PromptInput {
+    /// Split the request into the prompt itself and the date it's scheduled on, refusing prompts the watch couldn't show.
+    fn into_prompt(self) -> Result<(Prompt, Option<NaiveDate>), AdminError> {
+        let prompt = Prompt::from_input(self.red_prompt, self.blue_prompt, self.options, self.question_type)
+            .map_err(AdminError::InvalidPrompt)?;
+
+        Ok((prompt, self.scheduled_date))
+    }
+}
+
+/// Struct that represents the JSON body of a schedule prompt request, a null scheduled_date unschedules the prompt.
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde", deny_unknown_fields)]
+pub struct ScheduleInput {
+    scheduled_date: Option<NaiveDate>
+}
+
+/// Enum that represents why an admin request was refused.
+#[derive(Debug)]
+pub enum AdminError {
+    NotFound,                       // No prompt with that id.
+    InvalidPrompt(&'static str),    // The prompt's text or question type is unusable.
+    AlreadyRan,                     // The change touches a poll that already closed.
+    HasVotes,                       // The change touches today's poll, which already has votes.
+    DateTaken,                      // Another prompt is already scheduled on that date.
+    Storage(rusqlite::Error)
+}
+
+impl From<rusqlite::Error> for AdminError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e {
+            rusqlite::Error::SqliteFailure(ref failure, _) if failure.code == ErrorCode::ConstraintViolation => AdminError::DateTaken,
+            e => AdminError::Storage(e)
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for AdminError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let (status, message) = match self {
+            AdminError::NotFound => (Status::NotFound, "no prompt with that id"),
+            AdminError::InvalidPrompt(reason) => (Status::UnprocessableEntity, reason),
+            AdminError::AlreadyRan => (Status::Conflict, "that poll already ran and can't be changed"),
+            AdminError::HasVotes => (Status::Conflict, "today's poll already has votes and can't be changed"),
+            AdminError::DateTaken => (Status::Conflict, "another prompt is already scheduled on that date"),
+            AdminError::Storage(e) => {
+                error_!("admin API database error: {}", e);
+                (Status::InternalServerError, "database error")
+            }
+        };
+
+        status::Custom(status, message).respond_to(request)
+    }
+}
+
+impl PersistentData {
+    /// Grab every prompt in the prompts table, scheduled ones first in date order.
+    fn list_prompts(&self) -> Result<Vec<PromptRecord>, AdminError> {
+        let db_conn = self.pool.reader()?;
+        let mut query_statement = db_conn.prepare(
+            "SELECT id, question_type, scheduled_date FROM prompts
+            ORDER BY scheduled_date IS NULL, scheduled_date, id"
+        )?;
+
+        let prompts = query_statement
+            .query_map([], |row| PromptRecord::from_row(&db_conn, row))?
+            .collect::<rusqlite::Result<Vec<PromptRecord>>>()?;
+
+        Ok(prompts)
+    }
+
+    /// Grab a single prompt by id.
+    fn get_prompt(&self, id: i64) -> Result<PromptRecord, AdminError> {
+        fetch_prompt(&*self.pool.reader()?, id)
+    }
+
+    /// Add a new prompt, optionally scheduled on a date.
+    fn create_prompt(&self, prompt: PromptInput) -> Result<PromptRecord, AdminError> {
+        let (prompt, scheduled_date) = prompt.into_prompt()?;
+
+        let today = self.has_a_new_day_begun()?.date;
+        let db_conn = self.pool.writer();
+
+        check_date_editable(&db_conn, today, scheduled_date)?;
+        check_question_type(&db_conn, &prompt.question_type)?;
+
+        let transaction = db_conn.unchecked_transaction()?;
+        let id = poll::insert_prompt(&transaction, &prompt, scheduled_date)?;
+        transaction.commit()?;
+
+        // Today's poll follows whatever prompt now runs today, as long as nobody voted on it yet.
+        self.refresh_current_poll(&db_conn, today)?;
+
+        fetch_prompt(&db_conn, id)
+    }
+
+    /// Replace the text, type and date of a prompt that hasn't run yet.
+    fn update_prompt(&self, id: i64, prompt: PromptInput) -> Result<PromptRecord, AdminError> {
+        let (prompt, scheduled_date) = prompt.into_prompt()?;
+
+        let today = self.has_a_new_day_begun()?.date;
+        let db_conn = self.pool.writer();
+
+        let current_prompt = fetch_prompt(&db_conn, id)?;
+        check_prompt_editable(&db_conn, today, &current_prompt)?;
+        check_date_editable(&db_conn, today, scheduled_date)?;
+        check_question_type(&db_conn, &prompt.question_type)?;
+
+        let transaction = db_conn.unchecked_transaction()?;
+        transaction.execute(
+            "UPDATE prompts SET question_type = ?1, scheduled_date = ?2 WHERE id = ?3",
+            params!(prompt.question_type, scheduled_date, id)
+        )?;
+        poll::write_prompt_options(&transaction, id, &prompt.options)?;
+        transaction.commit()?;
+
+        self.refresh_current_poll(&db_conn, today)?;
+
+        fetch_prompt(&db_conn, id)
+    }
+
+    /// Move a prompt that hasn't run yet onto another date, or off the schedule.
+    fn schedule_prompt(&self, id: i64, scheduled_date: Option<NaiveDate>) -> Result<PromptRecord, AdminError> {
+        let today = self.has_a_new_day_begun()?.date;
+        let db_conn = self.pool.writer();
+
+        let current_prompt = fetch_prompt(&db_conn, id)?;
+        check_prompt_editable(&db_conn, today, &current_prompt)?;
+        check_date_editable(&db_conn, today, scheduled_date)?;
+
+        db_conn.execute("UPDATE prompts SET scheduled_date = ?1 WHERE id = ?2", params!(scheduled_date, id))?;
+        self.refresh_current_poll(&db_conn, today)?;
+
+        fetch_prompt(&db_conn, id)
+    }
+
+    /// Remove a prompt that hasn't run yet.
+    fn delete_prompt(&self, id: i64) -> Result<(), AdminError> {
+        let today = self.has_a_new_day_begun()?.date;
+        let db_conn = self.pool.writer();
+
+        let current_prompt = fetch_prompt(&db_conn, id)?;
+        check_prompt_editable(&db_conn, today, &current_prompt)?;
+
+        let transaction = db_conn.unchecked_transaction()?;
+        transaction.execute("DELETE FROM prompt_options WHERE prompt_id = ?1", [id])?;
+        transaction.execute("DELETE FROM prompts WHERE id = ?1", [id])?;
+        transaction.commit()?;
+
+        self.refresh_current_poll(&db_conn, today)?;
+
+        Ok(())
+    }
+}
+
+/// Grab a single prompt by id.
+fn fetch_prompt(db_conn: &Connection, id: i64) -> Result<PromptRecord, AdminError> {
+    db_conn.query_row(
+        "SELECT id, question_type, scheduled_date FROM prompts WHERE id = ?1",
+        [id],
+        |row| PromptRecord::from_row(db_conn, row)
+    ).optional()?.ok_or(AdminError::NotFound)
+}
+
+/// Check that the poll of the given date can still be changed.
+///
+/// Past polls are history and never change, today's poll only changes until its first vote comes in.
+fn check_date_editable(db_conn: &Connection, today: NaiveDate, scheduled_date: Option<NaiveDate>) -> Result<(), AdminError> {
+    let scheduled_date = match scheduled_date {
+        Some(scheduled_date) => scheduled_date,
+        None => return Ok(())
+    };
+
+    if scheduled_date < today {
+        return Err(AdminError::AlreadyRan);
+    }
+
+    if scheduled_date == today {
+        let has_votes = db_conn
+            .prepare("SELECT 1 FROM poll_options WHERE poll_date = ?1 AND vote_count > 0")?
+            .exists([scheduled_date])?;
+
+        if has_votes {
+            return Err(AdminError::HasVotes);
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that a prompt can still be changed, on top of its scheduled date.
+///
+/// Backlog and rerun prompts run without being scheduled, so every poll the prompt ran on counts.
+fn check_prompt_editable(db_conn: &Connection, today: NaiveDate, prompt: &PromptRecord) -> Result<(), AdminError> {
+    check_date_editable(db_conn, today, prompt.scheduled_date)?;
+
+    let ran_on = db_conn
+        .prepare("SELECT poll_date FROM polls WHERE prompt_id = ?1")?
+        .query_map([prompt.id], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<NaiveDate>>>()?;
+
+    for poll_date in ran_on {
+        check_date_editable(db_conn, today, Some(poll_date))?;
+    }
+
+    Ok(())
+}
+
+/// Refuse question types the watch has no heading for.
+fn check_question_type(db_conn: &Connection, question_type: &QuestionType) -> Result<(), AdminError> {
+    if !question_type::exists(db_conn, question_type)? {
+        return Err(AdminError::InvalidPrompt("unknown question_type, question types are defined in the prompt calendar"));
+    }
+
+    Ok(())
+}
+
+/// API endpoint for GET-ing every prompt.
+#[get("/prompts")]
+fn list_prompts(_admin: Admin, persistent_data: &State<PersistentData>) -> Result<Json<Vec<PromptRecord>>, AdminError> {
+    persistent_data.list_prompts().map(Json)
+}
+
+/// API endpoint for POST-ing a new prompt.
+#[post("/prompts", data = "<prompt>")]
+fn create_prompt(_admin: Admin, persistent_data: &State<PersistentData>, prompt: Json<PromptInput>) -> Result<status::Created<Json<PromptRecord>>, AdminError> {
+    let prompt = persistent_data.create_prompt(prompt.into_inner())?;
+
+    Ok(status::Created::new(format!("/api/rtv/admin/prompts/{}", prompt.id)).body(Json(prompt)))
+}
+
+/// API endpoint for GET-ing a single prompt.
+#[get("/prompts/<id>")]
+fn get_prompt(_admin: Admin, persistent_data: &State<PersistentData>, id: i64) -> Result<Json<PromptRecord>, AdminError> {
+    persistent_data.get_prompt(id).map(Json)
+}
+
+/// API endpoint for PUT-ing new text, type and date on a prompt.
+#[put("/prompts/<id>", data = "<prompt>")]
+fn update_prompt(_admin: Admin, persistent_data: &State<PersistentData>, id: i64, prompt: Json<PromptInput>) -> Result<Json<PromptRecord>, AdminError> {
+    persistent_data.update_prompt(id, prompt.into_inner()).map(Json)
+}
+
+/// API endpoint for PUT-ing the date a prompt runs on.
+#[put("/prompts/<id>/schedule", data = "<schedule>")]
+fn schedule_prompt(_admin: Admin, persistent_data: &State<PersistentData>, id: i64, schedule: Json<ScheduleInput>) -> Result<Json<PromptRecord>, AdminError> {
+    persistent_data.schedule_prompt(id, schedule.scheduled_date).map(Json)
+}
+
+/// API endpoint for DELETE-ing a prompt.
+#[delete("/prompts/<id>")]
+fn delete_prompt(_admin: Admin, persistent_data: &State<PersistentData>, id: i64) -> Result<Status, AdminError> {
+    persistent_data.delete_prompt(id).map(|_| Status::NoContent)
+}
+
+/// Every admin API endpoint, mounted at /api/rtv/admin/.
+pub fn routes() -> Vec<Route> {
+    routes![list_prompts, create_prompt, get_prompt, update_prompt, schedule_prompt, delete_prompt]
+}
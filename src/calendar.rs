@@ -0,0 +1,352 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+
+use rocket::serde::Deserialize;
+
+use chrono::{Datelike, Months, NaiveDate};
+use rusqlite::{Connection, OptionalExtension, params};
+use toml::Spanned;
+
+use crate::poll::{self, Prompt};
+use crate::question_type::{self, BUILT_IN_QUESTION_TYPES, QuestionType, QuestionTypeInfo};
+
+/// Struct that represents the raw layout of the prompt calendar file.
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde", deny_unknown_fields)]
+struct CalendarFile {
+    #[serde(default)]
+    prompts: Vec<CalendarEntry>,            // Every [[prompts]] table found in the file.
+    #[serde(default)]
+    backlog: Vec<Spanned<BacklogEntry>>,    // Every [[backlog]] table found in the file.
+    #[serde(default)]
+    question_types: Vec<Spanned<QuestionTypeEntry>> // Every [[question_types]] table found in the file.
+}
+
+/// Struct that represents a single [[prompts]] table of the prompt calendar file.
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde", deny_unknown_fields)]
+struct CalendarEntry {
+    date: Spanned<String>,          // Month and day the prompt runs on, formatted "MM-DD".
+    #[serde(default)]
+    red_prompt: Option<String>,     // Prompt shown on the red side of a two-option prompt.
+    #[serde(default)]
+    blue_prompt: Option<String>,    // Prompt shown on the blue side of a two-option prompt.
+    #[serde(default)]
+    options: Option<Vec<String>>,   // Every option of the prompt, instead of red_prompt and blue_prompt.
+    question_type: QuestionType     // QuestionType of the prompt.
+}
+
+/// Struct that represents a single [[backlog]] table of the prompt calendar file, a prompt without a date of its own.
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde", deny_unknown_fields)]
+struct BacklogEntry {
+    #[serde(default)]
+    red_prompt: Option<String>,     // Prompt shown on the red side of a two-option prompt.
+    #[serde(default)]
+    blue_prompt: Option<String>,    // Prompt shown on the blue side of a two-option prompt.
+    #[serde(default)]
+    options: Option<Vec<String>>,   // Every option of the prompt, instead of red_prompt and blue_prompt.
+    question_type: QuestionType     // QuestionType of the prompt.
+}
+
+/// Struct that represents a single [[question_types]] table of the prompt calendar file.
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde", deny_unknown_fields)]
+struct QuestionTypeEntry {
+    code: String,                   // Code prompts refer to the question type by, i.e. "HOT".
+    title: String,                  // Heading shown above polls of this type, i.e. "Hot Take".
+    #[serde(default)]
+    subtitle: Option<String>,       // Line shown under the heading, if any.
+    #[serde(default)]
+    icon_id: Option<String>         // Icon the watch shows next to the heading, if any.
+}
+
+/// Enum that represents what to run on a day the calendar has no prompt scheduled for.
+/// 
+/// Can be:
+/// backlog, the next unscheduled prompt, starting with the calendar's [[backlog]] pool
+/// rerun, the prompt of an earlier poll
+/// none, no poll at all that day
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum FallbackPolicy {
+    Backlog,
+    Rerun,
+    None
+}
+
+/// Error raised when the prompt calendar file can't be read or is malformed.
+#[derive(Debug)]
+pub struct CalendarError {
+    path: String,                   // Path of the calendar file that failed to load.
+    line: Option<usize>,            // Line of the offending entry, if the error can be pinned to one.
+    message: String                 // Human readable description of what went wrong.
+}
+
+impl fmt::Display for CalendarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "prompt calendar {}:{}: {}", self.path, line, self.message),
+            None => write!(f, "prompt calendar {}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// Struct that represents the yearly prompt calendar, keyed by (month, day), and the pool of backlog prompts.
+pub struct PromptCalendar {
+    prompts: HashMap<(u32, u32), Prompt>,
+    rerun_order: Vec<(u32, u32)>,           // Every scheduled (month, day), in calendar order.
+    backlog: Vec<Prompt>,
+    question_types: Vec<(QuestionType, QuestionTypeInfo)>   // Question types defined by the file, built-in ones it redefines included.
+}
+
+impl PromptCalendar {
+    /// Read and validate the prompt calendar at the given path.
+    ///
+    /// Every error is reported with the line of the file it was found on so the calendar can be fixed before the server starts.
+    pub fn load(path: &str) -> Result<Self, CalendarError> {
+        let source = fs::read_to_string(path).map_err(|e| CalendarError {
+            path: path.to_string(),
+            line: None,
+            message: e.to_string()
+        })?;
+
+        Self::parse(path, &source)
+    }
+
+    /// Parse the contents of a prompt calendar file.
+    fn parse(path: &str, source: &str) -> Result<Self, CalendarError> {
+        let error_at = |offset: Option<usize>, message: String| CalendarError {
+            path: path.to_string(),
+            line: offset.map(|offset| line_of(source, offset)),
+            message
+        };
+
+        let calendar_file: CalendarFile = toml::from_str(source)
+            .map_err(|e| error_at(e.span().map(|span| span.start), e.message().to_string()))?;
+
+        let mut question_types = Vec::new();
+        let mut known_codes: HashSet<QuestionType> = BUILT_IN_QUESTION_TYPES.iter()
+            .map(|(code, _)| QuestionType::parse(code).expect("built-in question type codes are valid"))
+            .collect();
+        let mut defined_lines = HashMap::new();
+
+        for entry in calendar_file.question_types {
+            let offset = entry.span().start;
+            let entry = entry.into_inner();
+
+            let code = QuestionType::parse(&entry.code).map_err(|message| error_at(Some(offset), message))?;
+
+            if entry.title.trim().is_empty() {
+                return Err(error_at(Some(offset), format!("question type {} has an empty title", entry.code)));
+            }
+
+            if let Some(first_line) = defined_lines.insert(code.clone(), line_of(source, offset)) {
+                return Err(error_at(Some(offset), format!("question type {} is already defined on line {}", entry.code, first_line)));
+            }
+
+            known_codes.insert(code.clone());
+            question_types.push((code, QuestionTypeInfo { title: entry.title.trim().to_string(), subtitle: entry.subtitle, icon_id: entry.icon_id }));
+        }
+
+        let check_question_type = |offset: usize, question_type: &QuestionType| {
+            if known_codes.contains(question_type) {
+                return Ok(());
+            }
+
+            Err(error_at(Some(offset), format!("unknown question_type \"{}\", define it in a [[question_types]] table", question_type.code())))
+        };
+
+        let mut prompts = HashMap::new();
+        let mut scheduled_lines = HashMap::new();
+
+        for entry in calendar_file.prompts {
+            let offset = entry.date.span().start;
+
+            // 2000 is a leap year, so "02-29" is accepted like every other real day of the year.
+            let date = NaiveDate::parse_from_str(&format!("2000-{}", entry.date.get_ref()), "%Y-%m-%d")
+                .map_err(|_| error_at(Some(offset), format!("invalid date \"{}\", expected \"MM-DD\"", entry.date.get_ref())))?;
+
+            check_question_type(offset, &entry.question_type)?;
+
+            let prompt = Prompt::from_input(entry.red_prompt, entry.blue_prompt, entry.options, entry.question_type)
+                .map_err(|reason| error_at(Some(offset), format!("prompt on {}: {}", entry.date.get_ref(), reason)))?;
+
+            let key = (date.month(), date.day());
+
+            if let Some(first_line) = scheduled_lines.insert(key, line_of(source, offset)) {
+                return Err(error_at(Some(offset), format!("{} is already scheduled on line {}", entry.date.get_ref(), first_line)));
+            }
+
+            prompts.insert(key, prompt);
+        }
+
+        let mut backlog = Vec::new();
+
+        for entry in calendar_file.backlog {
+            let offset = entry.span().start;
+            let entry = entry.into_inner();
+
+            check_question_type(offset, &entry.question_type)?;
+
+            let prompt = Prompt::from_input(entry.red_prompt, entry.blue_prompt, entry.options, entry.question_type)
+                .map_err(|reason| error_at(Some(offset), format!("backlog prompt: {}", reason)))?;
+
+            backlog.push(prompt);
+        }
+
+        let mut rerun_order: Vec<(u32, u32)> = prompts.keys().copied().collect();
+        rerun_order.sort();
+
+        Ok(PromptCalendar { prompts, rerun_order, backlog, question_types })
+    }
+
+    /// Grab the prompt scheduled on the given month (1..12) and day (1..31), if there is one.
+    pub fn get(&self, month: u32, day: u32) -> Option<&Prompt> {
+        self.prompts.get(&(month, day))
+    }
+
+    /// Grab the prompt that ran on the given date before prompts were kept in the database, falling back on the given
+    /// policy if the calendar has a gap that day.
+    /// 
+    /// Only used to attach polls tallied before the import to their prompt, so it follows the rules those builds ran with:
+    /// fallback prompts rotate through the file's backlog, or for rerun through the calendar's own days, one day at a time.
+    /// The database's fallback rules, which rerun the prompts of earlier polls, apply from the import on. None means there was no poll that day.
+    fn prompt_for(&self, date: NaiveDate, fallback_policy: FallbackPolicy) -> Option<&Prompt> {
+        if let Some(prompt) = self.get(date.month(), date.day()) {
+            return Some(prompt);
+        }
+
+        let rotation = date.num_days_from_ce() as usize;
+
+        match fallback_policy {
+            FallbackPolicy::Backlog if !self.backlog.is_empty() => Some(&self.backlog[rotation % self.backlog.len()]),
+            FallbackPolicy::Rerun if !self.rerun_order.is_empty() => self.prompts.get(&self.rerun_order[rotation % self.rerun_order.len()]),
+            _ => None
+        }
+    }
+
+    /// Add the calendar's question types to question_types, or update them if they're already there.
+    /// 
+    /// Unlike prompts this runs on every start, so a new question type or a reworded heading only takes a restart.
+    /// Question types removed from the file stay, prompts that already use them keep their heading.
+    pub fn sync_question_types(&self, db_conn: &Connection) -> rusqlite::Result<()> {
+        for (code, info) in &self.question_types {
+            question_type::upsert(db_conn, code, info)?;
+        }
+
+        Ok(())
+    }
+
+    /// Copy the calendar into the prompts table, the one time the database is set up from this file.
+    /// 
+    /// Polls that already ran get the prompt they ran with and backlog prompts are added unscheduled.
+    /// Dated prompts are scheduled by schedule_year_ahead, which keeps doing so every day after the import.
+    pub fn import(&self, db_conn: &Connection, fallback_policy: FallbackPolicy) -> rusqlite::Result<()> {
+        let mut past_polls_query_statement = db_conn.prepare("SELECT poll_date FROM polls WHERE prompt_id IS NULL")?;
+        let past_poll_dates = past_polls_query_statement
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<NaiveDate>>>()?;
+
+        // Those polls were tallied as red and blue, only a two-option prompt fits them.
+        for poll_date in past_poll_dates {
+            let mut prompt_id = scheduled_prompt_id(db_conn, poll_date)?;
+
+            if let Some(prompt) = self.prompt_for(poll_date, fallback_policy).filter(|prompt| prompt.sides().is_some()) {
+                if prompt_id.is_none() {
+                    prompt_id = Some(poll::insert_prompt(db_conn, prompt, Some(poll_date))?);
+                }
+            }
+
+            db_conn.execute("UPDATE polls SET prompt_id = ?1 WHERE poll_date = ?2", params!(prompt_id, poll_date))?;
+        }
+
+        for prompt in &self.backlog {
+            poll::insert_prompt(db_conn, prompt, None)?;
+        }
+
+        Ok(())
+    }
+
+    /// Schedule the calendar's dated prompts on every day up to a year ahead of today that isn't scheduled yet, so the calendar repeats every year.
+    /// 
+    /// The last day scheduled is kept in metadata as calendar_scheduled_through, so each day is only scheduled from the file once:
+    /// a day the admin API unscheduled stays a gap, and edits to the file only apply to days not scheduled yet, the ones more
+    /// than a year ahead of the edit. Days that already have a prompt scheduled through the admin API keep it.
+    pub fn schedule_year_ahead(&self, db_conn: &Connection, today: NaiveDate) -> rusqlite::Result<()> {
+        let scheduled_through = db_conn
+            .query_row("SELECT value FROM metadata WHERE key = 'calendar_scheduled_through'", [], |row| row.get(0))
+            .optional()?
+            .unwrap_or_else(|| today.pred_opt().expect("today is after the first representable date"));
+        let schedule_until = (today + Months::new(12)).pred_opt().expect("a year from today is after the first representable date");
+
+        if scheduled_through >= schedule_until {
+            return Ok(());
+        }
+
+        let transaction = db_conn.unchecked_transaction()?;
+
+        for date in scheduled_through.iter_days().skip(1).take_while(|date| *date <= schedule_until) {
+            if let Some(prompt) = self.get(date.month(), date.day()) {
+                if scheduled_prompt_id(&transaction, date)?.is_none() {
+                    poll::insert_prompt(&transaction, prompt, Some(date))?;
+                }
+            }
+        }
+
+        transaction.execute(
+            "INSERT INTO metadata (key, value) VALUES ('calendar_scheduled_through', ?1) ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            [schedule_until]
+        )?;
+
+        transaction.commit()
+    }
+}
+
+/// Grab the id of the prompt scheduled on the given date, if there is one.
+fn scheduled_prompt_id(db_conn: &Connection, scheduled_date: NaiveDate) -> rusqlite::Result<Option<i64>> {
+    db_conn
+        .query_row("SELECT id FROM prompts WHERE scheduled_date = ?1", [scheduled_date], |row| row.get(0))
+        .optional()
+}
+
+/// Turn a byte offset into the file into a 1-based line number.
+fn line_of(source: &str, offset: usize) -> usize {
+    source[..offset.min(source.len())].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROMPT: &str = "red_prompt = \"Cats\"\nblue_prompt = \"Dogs\"\nquestion_type = \"TOT\"\n";
+
+    fn parse_error(source: &str) -> CalendarError {
+        PromptCalendar::parse("prompts.toml", source).err().expect("the calendar should be refused")
+    }
+
+    #[test]
+    fn syntax_error_is_reported_on_its_line() {
+        let error = parse_error(&format!("[[prompts]]\ndate = \"03-10\"\n{}\n[[prompts]]\ndate = \"03-11\nred_prompt = \"Tea\"\n", PROMPT));
+
+        assert_eq!(error.line, Some(8));
+        assert!(error.to_string().starts_with("prompt calendar prompts.toml:8: "), "{}", error);
+    }
+
+    #[test]
+    fn invalid_date_is_reported_on_its_line() {
+        let error = parse_error(&format!("[[prompts]]\ndate = \"03-10\"\n{}\n[[prompts]]\ndate = \"13-01\"\n{}", PROMPT, PROMPT));
+
+        assert_eq!(error.line, Some(8));
+        assert_eq!(error.to_string(), "prompt calendar prompts.toml:8: invalid date \"13-01\", expected \"MM-DD\"");
+    }
+
+    #[test]
+    fn duplicate_date_is_reported_on_its_line_along_with_the_first_one() {
+        let error = parse_error(&format!("[[prompts]]\ndate = \"03-10\"\n{}\n[[prompts]]\ndate = \"03-10\"\n{}", PROMPT, PROMPT));
+
+        assert_eq!(error.line, Some(8));
+        assert_eq!(error.to_string(), "prompt calendar prompts.toml:8: 03-10 is already scheduled on line 2");
+    }
+}
@@ -0,0 +1,55 @@
+use chrono::{DateTime, Utc};
+
+#[cfg(test)]
+use std::sync::Mutex;
+
+#[cfg(test)]
+use chrono::Duration;
+
+/// Trait that represents where PersistentData gets the current time from.
+///
+/// Every "what day is it" decision goes through a Clock, so tests can swap in a FakeClock and walk polls across days.
+pub trait Clock: Send + Sync {
+    /// Current instant.
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// Clock backed by the system's real time.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Clock that only moves when a test tells it to.
+#[cfg(test)]
+pub struct FakeClock {
+    now: Mutex<DateTime<Utc>>       // Instant returned by now() until the test sets or advances it.
+}
+
+#[cfg(test)]
+impl FakeClock {
+    /// Create a new FakeClock stopped at the given instant.
+    pub fn new(now: DateTime<Utc>) -> Self {
+        FakeClock { now: Mutex::new(now) }
+    }
+
+    /// Move the clock to the given instant.
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    /// Move the clock forward by the given duration.
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+}
@@ -1,28 +1,43 @@
 #[macro_use] extern crate rocket;
 
-use std::sync::Mutex;
-
-use rocket::State;
+mod admin;
+mod calendar;
+mod clock;
+mod migrations;
+mod poll;
+mod pool;
+mod question_type;
+mod schedule;
+mod users;
+mod v2;
+mod voter;
+
+#[cfg(test)]
+mod tests;
+
+use std::fmt;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use rocket::{Build, Request, Rocket, State};
+use rocket::figment::Figment;
 use rocket::http::Status;
-use rocket::serde::{Serialize, json::Json};
+use rocket::response::{self, Responder, status};
+use rocket::serde::{Deserialize, Serialize, json::{self, Json}};
 
-use rusqlite::{Connection, Result, params};
+use rusqlite::{Connection, ErrorCode, OptionalExtension, Result, params};
 
-use chrono::{Datelike, Utc};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
 
-/// Enum that represents the type of daily question being asked.
-/// 
-/// Can be:
-/// Would You Rather
-/// Who Would Win
-/// This Or That
-#[derive(Serialize, Clone)]
-#[serde(crate = "rocket::serde")]
-enum QuestionType {
-    WYR,
-    WWW,
-    TOT
-}
+use admin::AdminToken;
+use calendar::{CalendarError, FallbackPolicy, PromptCalendar};
+use clock::{Clock, SystemClock};
+use migrations::{MigrationContext, MigrationError};
+use poll::{PollJSONResponse, Prompt, counts_of_poll, prompt_of_poll, results_of_poll};
+use pool::ConnectionPool;
+use question_type::{QuestionType, QuestionTypeInfo};
+use schedule::PollSchedule;
+use voter::{InvalidVoterId, VoterId, VoterIdHasher};
 
 /// Struct that represents JSON payload sent to RTV Pebble client.
 #[derive(Serialize)]
@@ -35,402 +50,855 @@ struct QuestionResultsJSONResponse {
     blue_count: u64                 // Current/Previous day's blue prompt vote count.
 }
 
+impl QuestionResultsJSONResponse {
+    /// Red and blue view of a poll, None unless the poll has exactly two options.
+    fn from_poll(poll: &PollJSONResponse) -> Option<Self> {
+        match poll.options.as_slice() {
+            [red, blue] => Some(QuestionResultsJSONResponse {
+                red_prompt: red.text.clone(),
+                blue_prompt: blue.text.clone(),
+                question_type: poll.question_type.clone(),
+                red_count: red.vote_count,
+                blue_count: blue.vote_count
+            }),
+            _ => None
+        }
+    }
+}
+
+/// Struct that represents JSON payload sent to RTV Pebble client when a request can't be served.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct ErrorJSONResponse {
+    error: &'static str,            // Stable, machine readable code, i.e. "already_voted".
+    message: &'static str           // Short human readable text the watch app can show as is.
+}
+
+/// Enum that represents the side a vote goes to, on a two-option poll.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+enum Choice {
+    Red,
+    Blue
+}
+
+/// Enum that represents the option a vote goes to.
+#[derive(Clone, Copy)]
+enum Ballot {
+    Side(Choice),                   // Red or blue, only two-option polls have sides.
+    Index(usize)                    // Index of the option, starting at 0.
+}
+
+impl Ballot {
+    /// Index of the option voted for on a poll with the given number of options.
+    fn option_index(self, option_count: usize) -> Result<usize, VoteError> {
+        match self {
+            Ballot::Side(_) if option_count != 2 => Err(VoteError::NotTwoOptions),
+            Ballot::Side(Choice::Red) => Ok(0),
+            Ballot::Side(Choice::Blue) => Ok(1),
+            Ballot::Index(option_index) if option_index < option_count => Ok(option_index),
+            Ballot::Index(_) => Err(VoteError::InvalidOption)
+        }
+    }
+}
+
+/// Struct that represents the JSON body of a vote request, which has either a choice or an option.
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde", deny_unknown_fields)]
+struct VoteInput {
+    voter_id: String,               // Voter id, in the format VoterId expects.
+    #[serde(default)]
+    choice: Option<Choice>,         // Side voted for on a two-option poll, "red" or "blue".
+    #[serde(default)]
+    option: Option<usize>,          // Index of the option voted for, starting at 0.
+    #[serde(default)]
+    poll_date: Option<NaiveDate>,   // Date of the poll the watch showed when the vote was cast, if it says.
+    #[serde(default)]
+    poll_id: Option<i64>            // Id of the poll the watch showed when the vote was cast, if it says. Only the v2 API hands out poll ids.
+}
+
+impl VoteInput {
+    /// What the vote picks, None unless exactly one of choice and option is given.
+    fn ballot(&self) -> Option<Ballot> {
+        match (self.choice, self.option) {
+            (Some(choice), None) => Some(Ballot::Side(choice)),
+            (None, Some(option_index)) => Some(Ballot::Index(option_index)),
+            _ => None
+        }
+    }
+}
+
+/// Struct that represents JSON payload sent to RTV Pebble client once a vote is counted or changed.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct VoteJSONResponse {
+    #[serde(flatten)]
+    results: Option<QuestionResultsJSONResponse>,   // Red and blue view of the poll's results on two-option polls, with this vote counted.
+    poll: PollJSONResponse,         // The poll's options and results, with this vote counted.
+    current_streak: u32,            // Polls in a row the voter voted on, with this vote counted. The rest of their stats is on /profile.
+    grace_deadline: DateTime<Utc>   // Until when the vote can still be switched to the other side.
+}
+
+/// Enum that represents why a vote wasn't counted or changed.
+#[derive(Debug)]
+enum VoteError {
+    AlreadyVoted,                   // The voter already voted on today's poll.
+    NotVoted,                       // The voter hasn't voted on today's poll, so there's no vote to change or retract.
+    GracePeriodOver,                // The voter's vote can't be changed anymore.
+    NoPollToday,                    // No prompt runs today.
+    WrongPoll,                      // The vote was cast for a poll other than today's, and isn't the previous one within the overlap.
+    InvalidOption,                  // The poll has no option with that index.
+    NotTwoOptions,                  // The vote picks red or blue, but the poll doesn't have exactly two options.
+    InvalidVote,                    // The vote request's body isn't a valid VoteInput.
+    InvalidVoterId,                 // The voter id isn't in the format VoterId expects.
+    Storage(rusqlite::Error)
+}
+
+impl From<rusqlite::Error> for VoteError {
+    fn from(e: rusqlite::Error) -> Self {
+        VoteError::Storage(e)
+    }
+}
+
+impl From<InvalidVoterId> for VoteError {
+    fn from(_: InvalidVoterId) -> Self {
+        VoteError::InvalidVoterId
+    }
+}
+
+impl<'r> Responder<'r, 'static> for VoteError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let (status, error, message) = match self {
+            VoteError::AlreadyVoted => (Status::Conflict, "already_voted", "You already voted today"),
+            VoteError::NotVoted => (Status::NotFound, "not_voted", "You haven't voted today"),
+            VoteError::GracePeriodOver => (Status::Conflict, "grace_period_over", "Too late to change your vote"),
+            VoteError::NoPollToday => (Status::NotFound, "no_poll_today", "No poll today"),
+            VoteError::WrongPoll => (Status::Conflict, "wrong_poll", "That poll isn't open for votes"),
+            VoteError::InvalidVote => (Status::BadRequest, "invalid_vote", "Invalid vote"),
+            VoteError::InvalidOption => (Status::BadRequest, "invalid_option", "That option isn't on the poll"),
+            VoteError::NotTwoOptions => (Status::Conflict, "not_two_options", "This poll has more than two options, update the app to vote"),
+            VoteError::InvalidVoterId => (Status::BadRequest, "invalid_voter_id", "Invalid voter id"),
+            VoteError::Storage(e) => {
+                error_!("vote database error: {}", e);
+                (Status::InternalServerError, "storage_error", "Vote not counted, try again")
+            }
+        };
+
+        status::Custom(status, Json(ErrorJSONResponse { error, message })).respond_to(request)
+    }
+}
+
+/// Enum that represents why a request about a voter, rather than a vote, failed.
+///
+/// A malformed voter id gets the same JSON body as on the vote routes, anything else keeps its plain text message.
+#[derive(Responder)]
+enum VoterRequestError {
+    InvalidVoterId(VoteError),
+    Failed(status::Custom<&'static str>)
+}
+
+impl From<InvalidVoterId> for VoterRequestError {
+    fn from(e: InvalidVoterId) -> Self {
+        VoterRequestError::InvalidVoterId(e.into())
+    }
+}
+
+impl From<status::Custom<&'static str>> for VoterRequestError {
+    fn from(e: status::Custom<&'static str>) -> Self {
+        VoterRequestError::Failed(e)
+    }
+}
+
+/// Struct that represents the RTV settings read from Rocket.toml or ROCKET_* environment variables.
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct RtvConfig {
+    #[serde(default = "RtvConfig::default_database_path")]
+    database_path: String,          // Path of the sqlite database file, i.e. ROCKET_DATABASE_PATH=/var/lib/rtv/wyr_persistent.db
+    #[serde(default = "RtvConfig::default_database_read_connections")]
+    database_read_connections: usize,// Read connections kept open on the database between requests.
+    #[serde(default = "RtvConfig::default_prompts_path")]
+    prompts_path: String,           // Path of the prompt calendar file, i.e. ROCKET_PROMPTS_PATH=/etc/rtv/prompts.toml
+    #[serde(default = "RtvConfig::default_rollover_time_zone")]
+    rollover_time_zone: Tz,         // IANA time zone the daily rollover happens in, i.e. ROCKET_ROLLOVER_TIME_ZONE=America/New_York
+    #[serde(default)]
+    rollover_hour: u32,             // Hour of the day (0..23) in rollover_time_zone the new prompt goes live at.
+    #[serde(default = "RtvConfig::default_fallback_policy")]
+    fallback_policy: FallbackPolicy,// What runs on days the calendar has no prompt for, i.e. ROCKET_FALLBACK_POLICY=backlog
+    #[serde(default = "RtvConfig::default_vote_grace_seconds")]
+    vote_grace_seconds: u32,        // Seconds after voting a voter can still switch sides, i.e. ROCKET_VOTE_GRACE_SECONDS=60, 0 disables switching.
+    #[serde(default = "RtvConfig::default_vote_overlap_seconds")]
+    vote_overlap_seconds: u32,      // Seconds after the rollover votes cast for the previous poll are still counted on it, 0 refuses them right away.
+    #[serde(default)]
+    admin_token: Option<String>,    // Bearer token of the admin API, the admin API is disabled if unset.
+    #[serde(default)]
+    voter_id_secret: Option<String> // Key voter ids are hashed with before they're stored, the server refuses to start if unset.
+}
+
+impl RtvConfig {
+    fn default_database_path() -> String {
+        "wyr_persistent.db".to_string()
+    }
+
+    fn default_database_read_connections() -> usize {
+        8
+    }
+
+    fn default_prompts_path() -> String {
+        "prompts.toml".to_string()
+    }
+
+    fn default_rollover_time_zone() -> Tz {
+        Tz::America__New_York
+    }
+
+    fn default_fallback_policy() -> FallbackPolicy {
+        FallbackPolicy::Rerun
+    }
+
+    fn default_vote_grace_seconds() -> u32 {
+        60
+    }
+
+    fn default_vote_overlap_seconds() -> u32 {
+        120
+    }
+}
+
+/// Enum that represents everything that can stop PersistentData from being created at startup.
+#[derive(Debug)]
+enum StartupError {
+    Database(rusqlite::Error),
+    Calendar(CalendarError),
+    Migration(MigrationError),
+    Config(String)
+}
+
+impl fmt::Display for StartupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StartupError::Database(e) => write!(f, "database: {}", e),
+            StartupError::Calendar(e) => write!(f, "{}", e),
+            StartupError::Migration(e) => write!(f, "database: {}", e),
+            StartupError::Config(reason) => write!(f, "config: {}", reason)
+        }
+    }
+}
+
+impl From<rusqlite::Error> for StartupError {
+    fn from(e: rusqlite::Error) -> Self {
+        StartupError::Database(e)
+    }
+}
+
+impl From<MigrationError> for StartupError {
+    fn from(e: MigrationError) -> Self {
+        StartupError::Migration(e)
+    }
+}
+
+impl From<CalendarError> for StartupError {
+    fn from(e: CalendarError) -> Self {
+        StartupError::Calendar(e)
+    }
+}
+
+/// Struct that represents the poll currently running, shared between requests without touching the database.
+struct CurrentPoll {
+    date: NaiveDate,                                    // Date of the poll.
+    prompt: Option<(Prompt, QuestionTypeInfo)>          // Prompt the poll runs with and the heading of its QuestionType, None if there's no poll that day.
+}
+
+impl CurrentPoll {
+    /// Date of the poll that ran the day before this one.
+    /// 
+    /// Plain date arithmetic, so the day before the 1st is the last day of the previous month (Dec 31 when it's Jan 1).
+    fn previous_date(&self) -> NaiveDate {
+        self.date.pred_opt().expect("poll date is after the first representable date")
+    }
+}
+
 /// Struct that represents data that needs to be shared with all db related functions.
+/// 
+/// Shared as is between Rocket's worker threads: reads go through their own connection of the pool
+/// and only writes line up behind the pool's writer.
 struct PersistentData {
-    db_conn: Connection,                                    // Open connection on sqlite database opened at new PersistentData.
-    rtv_prompts: [Vec<(String, String, QuestionType)>; 12], // RTV year prompts initialized at new PersistentData.
-    current_month: u32,                                     // Current month of the year's number.
-    current_day: u32                                        // Current day of the month's number.
+    pool: ConnectionPool,                               // Connections on the sqlite database opened at new PersistentData.
+    prompt_calendar: PromptCalendar,                    // Calendar file at prompts_path, scheduled a year ahead every day.
+    fallback_policy: FallbackPolicy,                    // What runs on days no prompt is scheduled for.
+    poll_schedule: PollSchedule,                        // Time zone and hour the daily rollover happens at.
+    clock: Arc<dyn Clock>,                              // Where the current time comes from, a FakeClock in tests.
+    voter_id_hasher: VoterIdHasher,                     // How voter ids are turned into what's stored in responders.
+    vote_grace_period: Duration,                        // How long after voting a voter can still switch sides.
+    vote_overlap: Duration,                             // How long after the rollover votes cast for the previous poll are still counted on it.
+    current_poll: RwLock<Option<Arc<CurrentPoll>>>      // Poll currently running, None until the first API call.
 }
 
 impl PersistentData {
-    /// Create a new instance of the mysqlite database file and initialize new database.
-    /// Database is generated on every launch, we do not current support picking up from where you left off.
-    fn new() -> Result<Self> {
-        let db_conn = Connection::open("wyr_persistent.db")?;
-    
-        db_conn.execute(
-            "CREATE TABLE IF NOT EXISTS vote_count (
-                question_number INT PRIMARY KEY,
-                red_vote_count INT,
-                blue_vote_count INT
-            )",
-            (),
-        )?;
-        
-        // Vec of a (String, String, QuestionType) tuple that represents a full year of prompts.
-        // Each index is a month (0..11), and each index is a vec of the tuple that represents the days of the month (0..x).
-        let rtv_prompts = [
-            // Jan
-            vec![
-
-            ],
-            // Feb
-            vec![
-
-            ],
-            // March
-            vec![
-                ("Move like a robot".to_string(), "Talk like a robot".to_string(), QuestionType::WYR),
-                ("Chocolate".to_string(), "Vanilla".to_string(), QuestionType::TOT),
-                ("Thor".to_string(), "The Hulk".to_string(), QuestionType::WWW),
-                ("Mario".to_string(), "Sonic".to_string(), QuestionType::WWW),
-                ("Roomba with a knife".to_string(), "Drone with a water gun".to_string(), QuestionType::WWW),
-                ("Have hair that changes color based on mood".to_string(), "Have glow in the dark skin".to_string(), QuestionType::WYR),
-                ("Star Wars".to_string(), "Star Trek".to_string(), QuestionType::TOT),
-                ("Coffee".to_string(), "Tea".to_string(), QuestionType::TOT),
-                ("Live without music".to_string(), "Live without TV".to_string(), QuestionType::WYR),
-                ("Link".to_string(), "Kratos".to_string(), QuestionType::WWW),
-                ("Tesla".to_string(), "Edison".to_string(), QuestionType::WWW),
-                ("Terraria".to_string(), "Minecraft".to_string(), QuestionType::TOT),
-                ("Spicy".to_string(), "Mild".to_string(), QuestionType::TOT),
-                ("Only whisper".to_string(), "Only shout".to_string(), QuestionType::WYR),
-                ("Tetris Blocks".to_string(), "Minecraft Blocks".to_string(), QuestionType::WWW),
-                ("Only talk in riddles".to_string(), "Only talk in emojis".to_string(), QuestionType::WYR),
-                ("Talk to plants".to_string(), "Talk to animals".to_string(), QuestionType::WYR),
-                ("Bowser".to_string(), "Dr. Robotnik".to_string(), QuestionType::WWW),
-                ("Hot Coffee".to_string(), "Iced Coffee".to_string(), QuestionType::TOT),
-                ("Cake".to_string(), "Pie".to_string(), QuestionType::TOT),
-                ("The Simpsons".to_string(), "Family Guy".to_string(), QuestionType::TOT),
-                ("Iron Man".to_string(), "Captian American".to_string(), QuestionType::WWW),
-                ("Godzilla".to_string(), "King Kong".to_string(), QuestionType::WWW),
-                ("Pizza".to_string(), "Burgers".to_string(), QuestionType::TOT),
-                ("Always burp confetti".to_string(), "Always sneeze glitter".to_string(), QuestionType::WYR),
-                ("Ice Cream".to_string(), "Cake".to_string(), QuestionType::TOT),
-                ("Have an extra eye".to_string(), "Have an extra ear".to_string(), QuestionType::WYR),
-                ("Wear a tuxedo to bed".to_string(), "Wear pajamas to every formal event".to_string(), QuestionType::WYR),
-                ("Have a mariachi band".to_string(), "Have a laugh track".to_string(), QuestionType::WYR),
-                ("Always wear clown shoes".to_string(), "Always wear a giant sombrero".to_string(), QuestionType::WYR),
-                ("A lion with eagle wings".to_string(), "A shark that can breathe air".to_string(), QuestionType::WWW),
-            ],
-            // April
-            vec![
-                ("Johnny English".to_string(),"Mr. Bean".to_string(), QuestionType::WWW),
-                ("The Beach".to_string(),"The Mountains".to_string(), QuestionType::TOT),
-                ("Be able to control fire".to_string(),"Be able to control water".to_string(), QuestionType::WYR),
-                ("You, literally".to_string(),"A horde of five year olds".to_string(), QuestionType::WWW),
-                ("iPhone".to_string(),"Android".to_string(), QuestionType::TOT),
-                ("Go to the future".to_string(),"Go to the past".to_string(), QuestionType::WYR),
-                ("One Trillion Lions".to_string(),"The Sun".to_string(), QuestionType::WWW),
-                ("Never use social media again".to_string(),"Never watch a movie again".to_string(), QuestionType::WYR),
-                ("Boba Fett".to_string(),"The Predator".to_string(), QuestionType::WWW),
-                ("Peanut butter".to_string(),"Jelly".to_string(), QuestionType::TOT),
-                ("Always be 10 minutes late".to_string(),"Always be 20 minutes early".to_string(), QuestionType::WYR),
-                ("The Kool-Aid Man".to_string(),"The Michelin Man".to_string(), QuestionType::WWW),
-                ("Halloween".to_string(),"Christmas".to_string(), QuestionType::TOT),
-                ("Go to the Moon".to_string(),"Go to Mars".to_string(), QuestionType::WYR),
-                ("Shark with bear hands".to_string(),"Bear with shark hands".to_string(), QuestionType::WWW),
-                ("PC".to_string(),"Console".to_string(), QuestionType::TOT),
-                ("Lose the ability to read".to_string(),"Lose the ability to speak".to_string(), QuestionType::WYR),
-                ("Waffles".to_string(),"Pancakes".to_string(), QuestionType::TOT),
-                ("Gandalf".to_string(),"Dumbledore".to_string(), QuestionType::WWW),
-                ("The city".to_string(),"The country".to_string(), QuestionType::TOT),
-                ("Be a famous director".to_string(),"Be a famous actor".to_string(), QuestionType::WYR),
-                ("Ned Flanders".to_string(),"Mr. Rogers".to_string(), QuestionType::WWW),
-                ("Have a bottomless box of Legos".to_string(),"Have a bottomless gas tank".to_string(), QuestionType::WYR),
-                ("Open gifts on Christmas Eve".to_string(),"Open gifts on Christmas Day".to_string(), QuestionType::WYR),
-                ("Bath".to_string(),"Shower".to_string(), QuestionType::TOT),
-                ("Spend the weekend with pirates".to_string(),"Spend the weekend with ninjas".to_string(), QuestionType::WYR),
-                ("Drink sour milk".to_string(),"Brush your teeth with soap".to_string(), QuestionType::WYR),
-                ("Grilled Cheese".to_string(),"Tacos".to_string(), QuestionType::TOT),
-                ("Goku".to_string(),"Superman".to_string(), QuestionType::WWW),
-                ("Master Chief".to_string(),"The Doom Slayer".to_string(), QuestionType::WWW)
-            ],
-            // May
-            vec![
-                ("Star in a TV show".to_string(),"Star in a movie".to_string(), QuestionType::WYR),
-                ("Big city".to_string(), "Small town".to_string(), QuestionType::TOT),
-                ("Spring".to_string(), "Fall".to_string(), QuestionType::TOT),
-                ("Be beautiful and stupid".to_string(),"Be unattractive but a genius".to_string(), QuestionType::WYR),
-                ("Coke".to_string(),"Pepsi".to_string(), QuestionType::TOT),
-                ("Day".to_string(), "Night".to_string(), QuestionType::TOT),
-                ("Neo".to_string(), "John Wick".to_string(), QuestionType::WWW),
-                ("Cold".to_string(), "Hot".to_string(), QuestionType::TOT),
-                ("Be able to fly".to_string(),"Be able to teleport".to_string(), QuestionType::WYR),
-                ("R2-D2".to_string(), "WALL-E".to_string(), QuestionType::WWW),
-                ("Dogs".to_string(),"Cats".to_string(), QuestionType::TOT),
-                ("James Bond".to_string(),"Jason Bourne".to_string(), QuestionType::WWW),
-                ("The Flash".to_string(), "Quicksilver".to_string(), QuestionType::WWW),
-                ("Batman".to_string(),"Superman".to_string(), QuestionType::WWW),
-                ("Summer".to_string(), "Winter".to_string(), QuestionType::TOT),
-                ("Have seven fingers on each hand".to_string(),"Have seven toes on each foot".to_string(), QuestionType::WYR),
-                ("Red".to_string(),"Blue".to_string(), QuestionType::TOT),
-                ("Team Fortress 2".to_string(), "Overwatch".to_string(), QuestionType::TOT),
-                ("Xbox".to_string(),"Playstation".to_string(), QuestionType::TOT),
-                ("Dine in".to_string(),"Eat out".to_string(), QuestionType::TOT),
-                ("Rain".to_string(), "Snow".to_string(), QuestionType::TOT),
-                ("Move like a robot".to_string(), "Talk like a robot".to_string(), QuestionType::WYR),
-                ("George Costanza".to_string(),"Jerry Seinfeld".to_string(), QuestionType::WWW),
-                ("Robocop".to_string(),"The Terminator (T-800)".to_string(), QuestionType::WWW),
-                ("Have a photographic memory".to_string(),"Be a genius".to_string(), QuestionType::WYR),
-                ("Yoda".to_string(), "Dumbledore".to_string(), QuestionType::WWW),
-                ("Hot weather".to_string(),"Cold weather".to_string(), QuestionType::TOT),
-                ("Have a pet dragon".to_string(), "Have a pet unicorn".to_string(), QuestionType::WYR),
-                ("live in a treehouse".to_string(), "live in a cave".to_string(), QuestionType::WYR),
-                ("Optimus Prime".to_string(), "Voltron".to_string(), QuestionType::WWW),
-                ("Summer".to_string(), "Winter".to_string(), QuestionType::TOT),        
-            ],
-            // June TBD
-            vec![
-                ("Comedy".to_string(), "Horror".to_string(), QuestionType::TOT),
-                ("Books".to_string(), "Movies".to_string(), QuestionType::TOT),
-                ("Zombies".to_string(), "Vampires".to_string(), QuestionType::TOT),
-                ("Aliens".to_string(), "Ghosts".to_string(), QuestionType::TOT),
-                ("Avatar".to_string(), "Dune".to_string(), QuestionType::TOT),
-                ("Pokémon".to_string(), "Digimon".to_string(), QuestionType::TOT),
-                ("Tintin".to_string(), "Indiana Jones".to_string(), QuestionType::TOT),
-                ("Nintendo".to_string(), "Sega".to_string(), QuestionType::TOT),
-                ("Smash Bros".to_string(), "Tekken".to_string(), QuestionType::TOT),
-                ("Street Fighter".to_string(), "Mortal Kombat".to_string(), QuestionType::TOT),
-                ("Guitar Hero".to_string(), "Dance Dance Revolution".to_string(), QuestionType::TOT),
-                ("Sneakers".to_string(), "Sandals".to_string(), QuestionType::TOT),
-                ("Homer Simpson".to_string(), "Peter Griffin".to_string(), QuestionType::WWW),
-            ],
-            // July
-            vec![
-
-            ],
-            // August
-            vec![
-
-            ],            
-            // September
-            vec![],
-            // October
-            vec![
-                ("Freddy Krueger".to_string(), "Pennywise".to_string(), QuestionType::WWW),
-                ("Dracula".to_string(), "Frankenstein".to_string(), QuestionType::WWW),
-                ("Jason Voorhees".to_string(), "Michael Myers".to_string(), QuestionType::WWW),
-                ("The Mummy".to_string(), "The Werewolf".to_string(), QuestionType::WWW),
-                ("Ghostface".to_string(), "Jigsaw".to_string(), QuestionType::WWW),
-                ("The Demogorgon".to_string(), "The Xenomorph".to_string(), QuestionType::WWW),
-            ],
-            // November
-            vec![
-
-            ],
-            // December
-            vec![
-
-            ]
-        ];
+    /// Open the mysqlite database file and bring its schema up to date by running any pending migrations.
+    /// Tallies and responders from earlier launches are kept, so a restart picks up today's poll where it left off.
+    /// The calendar file at prompts_path is imported into the prompts table the first time the database is set up,
+    /// and its dated prompts are scheduled a year ahead on every start. A malformed calendar stops the server from starting.
+    fn new(rtv_config: &RtvConfig, clock: Arc<dyn Clock>) -> Result<Self, StartupError> {
+        let pool = ConnectionPool::open(&rtv_config.database_path, rtv_config.database_read_connections)?;
+
+        let poll_schedule = PollSchedule::new(rtv_config.rollover_time_zone, rtv_config.rollover_hour).map_err(StartupError::Config)?;
+
+        let prompt_calendar = PromptCalendar::load(&rtv_config.prompts_path)?;
+
+        let voter_id_secret = rtv_config.voter_id_secret.as_deref()
+            .ok_or_else(|| StartupError::Config("voter_id_secret must be set, voter ids are stored hashed with it".to_string()))?;
+        let voter_id_hasher = VoterIdHasher::new(voter_id_secret).map_err(StartupError::Config)?;
+
+        {
+            let mut db_conn = pool.writer();
+
+            migrations::run(&mut db_conn, &MigrationContext { today: poll_schedule.poll_date_at(clock.now()), voter_id_hasher: &voter_id_hasher })?;
+
+            prompt_calendar.sync_question_types(&db_conn)?;
+
+            let is_calendar_imported = db_conn.prepare("SELECT 1 FROM metadata WHERE key = 'calendar_imported_at'")?.exists([])?;
+
+            if !is_calendar_imported {
+                let transaction = db_conn.transaction()?;
+
+                prompt_calendar.import(&transaction, rtv_config.fallback_policy)?;
+                transaction.execute(
+                    "INSERT INTO metadata (key, value) VALUES ('calendar_imported_at', ?1)",
+                    [clock.now().to_rfc3339()]
+                )?;
+
+                transaction.commit()?;
+            }
+
+            prompt_calendar.schedule_year_ahead(&db_conn, poll_schedule.poll_date_at(clock.now()))?;
+        }
     
         Ok(
             PersistentData { 
-                db_conn,
-                rtv_prompts,
-                current_month: 0,
-                current_day: 0
+                pool,
+                prompt_calendar,
+                fallback_policy: rtv_config.fallback_policy,
+                poll_schedule,
+                clock,
+                voter_id_hasher,
+                vote_grace_period: Duration::seconds(rtv_config.vote_grace_seconds.into()),
+                vote_overlap: Duration::seconds(rtv_config.vote_overlap_seconds.into()),
+                current_poll: RwLock::new(None)
             }
         )
     }
 
-    /// For every API call done by user, check if it's been a full day since the last API call.
+    /// For every API call done by user, check if it's been a full day since the last API call, and grab the poll currently running.
     /// 
     /// This is performed "on demand" so we don't have to keep a timer/loop checking to see if a new day has begun.
     /// The operations for a new day are very light so there won't be "lag" on the user who kicks off the new day.
     /// After all, if no one is using the API does it even exist? 
-    fn has_a_new_day_begun(&mut self) -> Result<()> {
-        let current_date = Utc::now();
+    fn has_a_new_day_begun(&self) -> Result<Arc<CurrentPoll>> {
+        let current_date = self.poll_schedule.poll_date_at(self.clock.now());
 
-        // If it's a new day...
-        // Create new day's result table.
-        // Drop blacklist of previous day's responders.
-        if (current_date.day() != self.current_day) {
-            self.db_conn.execute(
-                "INSERT INTO vote_count (question_number, red_vote_count, blue_vote_count) VALUES (?1, 0, 0)", 
-            params!(&current_date.day()))?;
-
-            self.db_conn.execute(
-                "DROP TABLE IF EXISTS responders",
-                ()
-            )?;
-        
-            self.db_conn.execute(
-                "CREATE TABLE responders (
-                    voter_id CHARACTER(16) PRIMARY KEY,
-                    response BOOLEAN NOT NULL
-                )",
-                (),
-            )?;
-
-            self.current_day = current_date.day();
-            self.current_month = current_date.month();
+        if let Some(current_poll) = self.cached_poll(current_date) {
+            return Ok(current_poll);
         }
 
-        Ok(())
+        let db_conn = self.pool.writer();
+
+        // Another request may have started the new day while this one waited for the writer.
+        if let Some(current_poll) = self.cached_poll(current_date) {
+            return Ok(current_poll);
+        }
+
+        // If it's a new day...
+        // Schedule the calendar's prompt of the day a year from now, so the calendar repeats every year.
+        self.prompt_calendar.schedule_year_ahead(&db_conn, current_date)?;
+
+        // Create new day's result row, unless it already exists from before a restart or there's no poll today.
+        // Responders are kept per poll, so the new day starts with nobody having voted.
+        self.refresh_current_poll(&db_conn, current_date)
     }
 
-    /// Grab day's prompt by index from rtv_prompts vec.
-    fn get_latest_prompts(&self) -> (String, String, QuestionType) {
-        return (self.rtv_prompts[(self.current_month - 1) as usize][(self.current_day - 1) as usize].clone());
+    /// Grab the cached current poll, if it's the poll of the given date.
+    fn cached_poll(&self, poll_date: NaiveDate) -> Option<Arc<CurrentPoll>> {
+        self.current_poll.read().unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .filter(|current_poll| current_poll.date == poll_date)
+            .cloned()
     }
 
-    /// Grab previous day's prompt by index of current day minus one from rtv_prompts vec.
-    fn get_previous_prompts(&self) -> (String, String, QuestionType) {
-        // Check if we're on the first day of the month, because we don't want to underflow!
-        if (self.current_day == 1) {
-            return self.rtv_prompts[(self.current_month - 1) as usize][29].clone();
-        } else {
-            return self.rtv_prompts[(self.current_month - 1) as usize][((self.current_day - 1) - 1) as usize].clone();
+    /// Sync the result row of the given date with the prompt that runs that day and cache it as the current poll.
+    /// 
+    /// Takes the writer, so whoever changes which prompt runs today calls this before letting go of it.
+    fn refresh_current_poll(&self, db_conn: &Connection, poll_date: NaiveDate) -> Result<Arc<CurrentPoll>> {
+        self.sync_poll_row(db_conn, poll_date)?;
+
+        let prompt = match prompt_of_poll(db_conn, poll_date)? {
+            Some(prompt) => {
+                let question_type_info = question_type::info_of(db_conn, &prompt.question_type)?;
+                Some((prompt, question_type_info))
+            },
+            None => None
+        };
+
+        let current_poll = Arc::new(CurrentPoll { date: poll_date, prompt });
+
+        *self.current_poll.write().unwrap_or_else(PoisonError::into_inner) = Some(current_poll.clone());
+
+        Ok(current_poll)
+    }
+
+    /// Make sure the given date has a poll row pinned to the prompt that runs that day, with a zeroed count for each
+    /// of the prompt's options, or no row if there's no poll that day.
+    /// 
+    /// A poll that already has votes is never changed.
+    fn sync_poll_row(&self, db_conn: &Connection, poll_date: NaiveDate) -> Result<()> {
+        let has_votes = db_conn.prepare("SELECT 1 FROM poll_options WHERE poll_date = ?1 AND vote_count > 0")?.exists([poll_date])?;
+
+        if has_votes {
+            return Ok(());
         }
+
+        let transaction = db_conn.unchecked_transaction()?;
+
+        transaction.execute("DELETE FROM poll_options WHERE poll_date = ?1", [poll_date])?;
+
+        match self.resolve_prompt_id(&transaction, poll_date)? {
+            Some(prompt_id) => {
+                transaction.execute(
+                    "INSERT INTO polls (poll_date, prompt_id) VALUES (?1, ?2) ON CONFLICT (poll_date) DO UPDATE SET prompt_id = excluded.prompt_id",
+                    params!(poll_date, prompt_id)
+                )?;
+                transaction.execute(
+                    "INSERT INTO poll_options (poll_date, option_index, vote_count) SELECT ?1, option_index, 0 FROM prompt_options WHERE prompt_id = ?2",
+                    params!(poll_date, prompt_id)
+                )?;
+            },
+            None => {
+                transaction.execute("DELETE FROM polls WHERE poll_date = ?1", [poll_date])?;
+            }
+        }
+
+        transaction.commit()
     }
 
-    /// Verify user calling RTV API has not previously voted.
+    /// Pick the id of the prompt that should run on the given date, None if there's no poll that day.
     /// 
-    /// This is done by taking the Pebble client's UUID and seeing if it exists in our responder's blacklist table.
-    fn has_user_voted(&mut self, voter_uuid: &String) -> Result<bool> {
-        self.has_a_new_day_begun()?;
+    /// The prompt scheduled on that date comes first. Otherwise the fallback policy rotates, one day at a time, through
+    /// the unscheduled prompts (backlog) or the prompts of earlier polls (rerun).
+    fn resolve_prompt_id(&self, db_conn: &Connection, poll_date: NaiveDate) -> Result<Option<i64>> {
+        let scheduled_prompt_id = db_conn
+            .query_row("SELECT id FROM prompts WHERE scheduled_date = ?1", [poll_date], |row| row.get(0))
+            .optional()?;
+
+        if scheduled_prompt_id.is_some() {
+            return Ok(scheduled_prompt_id);
+        }
 
-        let mut does_uuid_exist_query_statement = self.db_conn.prepare("SELECT * FROM responders WHERE voter_id = ?1")?;
+        let candidate_ids = match self.fallback_policy {
+            FallbackPolicy::Backlog => db_conn
+                .prepare("SELECT id FROM prompts WHERE scheduled_date IS NULL ORDER BY id")?
+                .query_map([], |row| row.get(0))?
+                .collect::<Result<Vec<i64>>>()?,
+            FallbackPolicy::Rerun => db_conn
+                .prepare("SELECT DISTINCT prompt_id FROM polls WHERE poll_date < ?1 AND prompt_id IS NOT NULL ORDER BY prompt_id")?
+                .query_map([poll_date], |row| row.get(0))?
+                .collect::<Result<Vec<i64>>>()?,
+            FallbackPolicy::None => return Ok(None)
+        };
+
+        if candidate_ids.is_empty() {
+            return Ok(None);
+        }
 
-        return does_uuid_exist_query_statement.exists([voter_uuid])
+        Ok(Some(candidate_ids[poll_date.num_days_from_ce() as usize % candidate_ids.len()]))
     }
-    
-    /// Grab a user's vote and increment the count for that choice. Add voted user to blacklist table so they can't vote again.
-    fn db_increment(&mut self, voter_uuid: String, which_increment: bool) -> Result<()> {    
-        self.has_a_new_day_begun()?;
 
-        // Check if user has not previously voted...
-        if !self.has_user_voted(&voter_uuid)? {
-            let mut get_current_vote_count_query_statement =  self.db_conn.prepare("SELECT red_vote_count, blue_vote_count FROM vote_count WHERE question_number = ?1")?;
+    /// Verify user calling RTV API has not previously voted.
+    /// 
+    /// This is done by taking the Pebble client's UUID and seeing if its hash exists in our responder's blacklist table for the current poll.
+    fn has_user_voted(&self, voter_id: &VoterId) -> Result<bool> {
+        let current_poll = self.has_a_new_day_begun()?;
+
+        let db_conn = self.pool.reader()?;
+        let mut does_uuid_exist_query_statement = db_conn.prepare("SELECT * FROM responders WHERE poll_date = ?1 AND voter_id = ?2")?;
 
-            get_current_vote_count_query_statement.query_row([self.current_day], |row|{
-                let current_red_vote_count: u64 = row.get(0)?;
-                let current_blue_vote_count: u64 = row.get(1)?;
-    
-                // true increment red, false increment blue
-                if (which_increment) {
-                    self.db_conn.execute(
-                        "UPDATE vote_count SET red_vote_count=?1 WHERE question_number=?2",
-                        (current_red_vote_count + 1, self.current_day),
-                    )?;
-                } else {
-                    self.db_conn.execute(
-                        "UPDATE vote_count SET blue_vote_count=?1 WHERE question_number=?2",
-                        (current_blue_vote_count + 1, self.current_day),
-                    )?;
-                }
-
-                self.db_conn.execute(
-                    "INSERT INTO responders (voter_id, response) VALUES (?1, ?2)",
-                    params!(voter_uuid, which_increment),
-                )?;
+        does_uuid_exist_query_statement.exists(params!(current_poll.date, self.voter_id_hasher.hash(current_poll.date, voter_id.as_str())))
+    }
     
-                Ok(())
-            })?;
-        
-            return Ok(())
+    /// Grab a user's vote and increment the count for that option. Add voted user to blacklist table so they can't vote again.
+    /// 
+    /// The voter record and the increment are written in one transaction, so either both make it to the database or neither does.
+    /// Whether the user already voted is left to the (poll_date, voter_id) primary key of responders, so two votes racing
+    /// each other can't both get in.
+    /// 
+    /// A vote that says which poll_date it was cast for goes to that poll, not whichever one is running by the time it arrives:
+    /// a vote for the previous poll is still counted on it for vote_overlap after the rollover, any other poll refuses it.
+    /// 
+    /// Returns the date of the poll the vote was counted on and until when the vote can be switched to another option.
+    /// Only votes on today's poll can be switched, a vote counted on the previous poll can't be, its deadline is when it was cast.
+    /// A vote cast again after a retraction counts as cast when the retracted one was, so it keeps that vote's deadline.
+    fn db_increment(&self, voter_id: &VoterId, ballot: Ballot, poll_date: Option<NaiveDate>) -> Result<(NaiveDate, DateTime<Utc>), VoteError> {    
+        let current_poll = self.has_a_new_day_begun()?;
+        let now = self.clock.now();
+
+        let poll_date = poll_date.unwrap_or(current_poll.date);
+
+        let no_poll_error = if poll_date == current_poll.date {
+            VoteError::NoPollToday
+        } else if poll_date == current_poll.previous_date() && now < self.poll_schedule.opens_at(current_poll.date) + self.vote_overlap {
+            VoteError::WrongPoll
         } else {
-            return Err(rusqlite::Error::ExecuteReturnedResults)
+            return Err(VoteError::WrongPoll);
+        };
+
+        let hashed_voter_id = self.voter_id_hasher.hash(poll_date, voter_id.as_str());
+
+        let mut db_conn = self.pool.writer();
+        let transaction = db_conn.transaction()?;
+
+        let option_count = option_count(&transaction, poll_date)?;
+
+        if option_count == 0 {
+            return Err(no_poll_error);
         }
 
+        let option_index = ballot.option_index(option_count)?;
+
+        let voted_at: DateTime<Utc> = transaction.query_row(
+            "SELECT voted_at FROM retracted_votes WHERE poll_date = ?1 AND voter_id = ?2",
+            params!(poll_date, hashed_voter_id),
+            |row| row.get(0)
+        ).optional()?.unwrap_or(now);
+
+        match transaction.execute(
+            "INSERT INTO responders (poll_date, voter_id, option_index, voted_at) VALUES (?1, ?2, ?3, ?4)",
+            params!(poll_date, hashed_voter_id, option_index, voted_at),
+        ) {
+            Err(rusqlite::Error::SqliteFailure(failure, _)) if failure.code == ErrorCode::ConstraintViolation => return Err(VoteError::AlreadyVoted),
+            result => result?
+        };
+
+        transaction.execute(
+            "UPDATE poll_options SET vote_count = vote_count + 1 WHERE poll_date = ?1 AND option_index = ?2",
+            params!(poll_date, option_index)
+        )?;
+
+        transaction.commit()?;
+
+        let grace_deadline = if poll_date == current_poll.date { voted_at + self.vote_grace_period } else { voted_at };
+
+        Ok((poll_date, grace_deadline))
     }
-    
-    /// Grab latest count for the current day's choices.
-    fn db_latest_count(&mut self) -> Result<(u64, u64)> {
-        self.has_a_new_day_begun()?;
 
-        let mut query_statement =  self.db_conn.prepare("SELECT red_vote_count, blue_vote_count FROM vote_count WHERE question_number=?")?;
-    
-        let query_result = query_statement.query_row([self.current_day], |row|{
-            Ok((row.get(0)?, row.get(1)?))
-        })?;
+    /// Move a user's vote on today's poll to another option, as long as they voted less than the grace period ago.
+    /// 
+    /// Without an option the vote moves to the other side, which only two-option polls have.
+    /// The voter record and both counters change in one transaction. Switching doesn't restart the grace period,
+    /// so the returned deadline is the one the original vote got. Returns it along with the date of the poll the vote is on.
+    fn db_switch(&self, voter_id: &VoterId, to_option: Option<usize>) -> Result<(NaiveDate, DateTime<Utc>), VoteError> {
+        let current_poll = self.has_a_new_day_begun()?;
+        let hashed_voter_id = self.voter_id_hasher.hash(current_poll.date, voter_id.as_str());
+
+        let mut db_conn = self.pool.writer();
+        let transaction = db_conn.transaction()?;
+
+        let (from_option, voted_at): (usize, Option<DateTime<Utc>>) = transaction.query_row(
+            "SELECT option_index, voted_at FROM responders WHERE poll_date = ?1 AND voter_id = ?2",
+            params!(current_poll.date, hashed_voter_id),
+            |row| Ok((row.get(0)?, row.get(1)?))
+        ).optional()?.ok_or(VoteError::NotVoted)?;
+
+        // Votes from before voted_at was recorded are past any grace period.
+        let grace_deadline = voted_at.ok_or(VoteError::GracePeriodOver)? + self.vote_grace_period;
+
+        if self.clock.now() > grace_deadline {
+            return Err(VoteError::GracePeriodOver);
+        }
 
-        Ok(query_result)
+        // Red is option 0 and blue option 1, so the other side of a two-option poll is 1 - from_option.
+        let to_option = match to_option {
+            Some(to_option) => Ballot::Index(to_option),
+            None => Ballot::Side(if from_option == 0 { Choice::Blue } else { Choice::Red })
+        }.option_index(option_count(&transaction, current_poll.date)?)?;
+
+        transaction.execute(
+            "UPDATE responders SET option_index = ?1 WHERE poll_date = ?2 AND voter_id = ?3",
+            params!(to_option, current_poll.date, hashed_voter_id)
+        )?;
+        transaction.execute(
+            "UPDATE poll_options SET vote_count = vote_count - 1 WHERE poll_date = ?1 AND option_index = ?2",
+            params!(current_poll.date, from_option)
+        )?;
+        transaction.execute(
+            "UPDATE poll_options SET vote_count = vote_count + 1 WHERE poll_date = ?1 AND option_index = ?2",
+            params!(current_poll.date, to_option)
+        )?;
+
+        transaction.commit()?;
+
+        Ok((current_poll.date, grace_deadline))
     }
+    
+    /// Take a user's vote on today's poll back out, so the poll reads as if they never voted (and they can vote again),
+    /// as long as they voted less than the grace period ago.
+    /// 
+    /// The voter record goes, the counter it added to goes down and a vote_audit entry records the retraction,
+    /// all in one transaction. The audit entry says which option lost a vote and when, not who retracted.
+    /// When the vote was cast is kept in retracted_votes, so voting again doesn't restart the grace period.
+    fn db_retract(&self, voter_id: &VoterId) -> Result<(), VoteError> {
+        let current_poll = self.has_a_new_day_begun()?;
+        let hashed_voter_id = self.voter_id_hasher.hash(current_poll.date, voter_id.as_str());
+
+        let mut db_conn = self.pool.writer();
+        let transaction = db_conn.transaction()?;
+
+        let (option_index, voted_at): (usize, Option<DateTime<Utc>>) = transaction.query_row(
+            "SELECT option_index, voted_at FROM responders WHERE poll_date = ?1 AND voter_id = ?2",
+            params!(current_poll.date, hashed_voter_id),
+            |row| Ok((row.get(0)?, row.get(1)?))
+        ).optional()?.ok_or(VoteError::NotVoted)?;
+
+        // Votes from before voted_at was recorded are past any grace period.
+        let voted_at = voted_at.ok_or(VoteError::GracePeriodOver)?;
+
+        if self.clock.now() > voted_at + self.vote_grace_period {
+            return Err(VoteError::GracePeriodOver);
+        }
+
+        transaction.execute(
+            "DELETE FROM responders WHERE poll_date = ?1 AND voter_id = ?2",
+            params!(current_poll.date, hashed_voter_id)
+        )?;
 
-    /// Grab latest count for the previous day's choices.
-    fn db_previous_count(&mut self) -> Result<(u64, u64)> {
-        self.has_a_new_day_begun()?;
+        transaction.execute(
+            "INSERT OR IGNORE INTO retracted_votes (poll_date, voter_id, voted_at) VALUES (?1, ?2, ?3)",
+            params!(current_poll.date, hashed_voter_id, voted_at)
+        )?;
+
+        transaction.execute(
+            "UPDATE poll_options SET vote_count = vote_count - 1 WHERE poll_date = ?1 AND option_index = ?2",
+            params!(current_poll.date, option_index)
+        )?;
 
-        let mut query_statement =  self.db_conn.prepare("SELECT red_vote_count, blue_vote_count FROM vote_count WHERE question_number=?")?;
+        transaction.execute(
+            "INSERT INTO vote_audit (poll_date, action, option_index, recorded_at) VALUES (?1, 'retract', ?2, ?3)",
+            params!(current_poll.date, option_index, self.clock.now())
+        )?;
+
+        transaction.commit()?;
+
+        Ok(())
+    }
     
-        let query_result = query_statement.query_row([(if (self.current_day == 1){30}else{self.current_day} - 1) as usize], |row|{
-            Ok((row.get(0)?, row.get(1)?))
-        })?;
+    /// Grab the prompt and results of the poll of the given date, None if there was no poll that day.
+    /// 
+    /// The current poll is laid out from its cached prompt, so only its counts are read, other polls are read in full.
+    fn poll_results(&self, poll_date: NaiveDate) -> Result<Option<PollJSONResponse>> {
+        let db_conn = self.pool.reader()?;
+
+        match self.cached_poll(poll_date) {
+            Some(current_poll) => match &current_poll.prompt {
+                Some((prompt, question_type_info)) => Ok(Some(PollJSONResponse::new(poll_date, prompt, question_type_info.clone(), counts_of_poll(&db_conn, poll_date)?))),
+                None => Ok(None)
+            },
+            None => results_of_poll(&db_conn, poll_date)
+        }
+    }
 
-        Ok(query_result)
+    /// Build what the watch gets back once a vote on the poll of the given date is counted or switched.
+    fn vote_response(&self, voter_id: &VoterId, poll_date: NaiveDate, grace_deadline: DateTime<Utc>) -> Result<VoteJSONResponse, VoteError> {
+        let poll = self.poll_results(poll_date)?.ok_or(VoteError::NoPollToday)?;
+
+        Ok(VoteJSONResponse {
+            results: QuestionResultsJSONResponse::from_poll(&poll),
+            poll,
+            current_streak: self.current_streak(voter_id)?,
+            grace_deadline
+        })
+    }
+
+    /// Instant the current poll closes and the next day's prompt goes live.
+    fn next_rollover(&self) -> DateTime<Utc> {
+        self.poll_schedule.next_rollover(self.clock.now())
     }
 }
 
+/// Count the options of the poll of the given date, 0 if there's no poll running that day.
+fn option_count(db_conn: &Connection, poll_date: NaiveDate) -> Result<usize> {
+    db_conn.query_row(
+        "SELECT COUNT(*) FROM poll_options JOIN polls ON polls.poll_date = poll_options.poll_date
+        WHERE poll_options.poll_date = ?1 AND polls.prompt_id IS NOT NULL",
+        [poll_date],
+        |row| row.get(0)
+    )
+}
+
+/// Log a database error hit while serving a request and turn it into a 500 with the given message.
+fn storage_error(e: rusqlite::Error, message: &'static str) -> status::Custom<&'static str> {
+    error_!("database error: {}", e);
+    status::Custom(Status::InternalServerError, message)
+}
+
+/// API endpoint for POST-ing a vote, with the voter id, the option voted for and optionally the poll's date in a JSON body.
+/// 
+/// The option is given either as its index ("option") or, on two-option polls, as a side ("choice": "red" or "blue").
+/// Responds with the poll's prompt and results with the vote counted, the voter's current streak,
+/// and until when the vote can be switched, so the watch doesn't have to ask for any of it after voting.
+/// A poll_id is refused as an invalid vote, poll ids only exist in the v2 API.
+#[post("/vote", data = "<vote>")]
+fn post_vote(persistent_data: &State<PersistentData>, vote: Result<Json<VoteInput>, json::Error<'_>>) -> Result<Json<VoteJSONResponse>, VoteError> {
+    let vote = vote.map_err(|_| VoteError::InvalidVote)?.into_inner();
+    let ballot = vote.ballot().filter(|_| vote.poll_id.is_none()).ok_or(VoteError::InvalidVote)?;
+    let voter_id = VoterId::parse(&vote.voter_id)?;
+
+    let (poll_date, grace_deadline) = persistent_data.db_increment(&voter_id, ballot, vote.poll_date)?;
+
+    persistent_data.vote_response(&voter_id, poll_date, grace_deadline).map(Json)
+}
+
 /// API endpoint for POST-ing vote for red choice.
+/// 
+/// Kept for older app builds, counts the same as POST-ing {"choice": "red"} to /vote but responds with an empty 200,
+/// the body those builds expect.
 #[post("/increment_red/<voter_uuid>")]
-fn post_increment_red(persistent_data: &State<Mutex<PersistentData>>, voter_uuid: String) -> Status {
-    return match persistent_data.lock().unwrap().db_increment(voter_uuid, true) {
-        Ok(_) => Status::Ok,
-        Err(_) => Status::InternalServerError
-    }
+fn post_increment_red(persistent_data: &State<PersistentData>, voter_uuid: Result<VoterId, InvalidVoterId>) -> Result<Status, VoteError> {
+    persistent_data.db_increment(&voter_uuid?, Ballot::Side(Choice::Red), None)?;
+
+    Ok(Status::Ok)
 }
 
 /// API endpoint for POST-ing vote for blue choice.
+/// 
+/// Kept for older app builds, counts the same as POST-ing {"choice": "blue"} to /vote but responds with an empty 200,
+/// the body those builds expect.
 #[post("/increment_blue/<voter_uuid>")]
-fn post_increment_blue(persistent_data: &State<Mutex<PersistentData>>, voter_uuid: String) -> Status {
-    return match persistent_data.lock().unwrap().db_increment(voter_uuid, false) {
-        Ok(_) => Status::Ok,
-        Err(_) => Status::InternalServerError
-    }
+fn post_increment_blue(persistent_data: &State<PersistentData>, voter_uuid: Result<VoterId, InvalidVoterId>) -> Result<Status, VoteError> {
+    persistent_data.db_increment(&voter_uuid?, Ballot::Side(Choice::Blue), None)?;
+
+    Ok(Status::Ok)
+}
+
+/// API endpoint for PUT-ing a voter's vote on today's poll onto another option, within the grace period after voting.
+/// 
+/// Moves the vote to the given option, or to the other side of a two-option poll if none is given.
+/// Responds like a vote, with the original grace deadline.
+#[put("/vote/<voter_uuid>/switch?<option>")]
+fn put_switch_vote(persistent_data: &State<PersistentData>, voter_uuid: Result<VoterId, InvalidVoterId>, option: Option<usize>) -> Result<Json<VoteJSONResponse>, VoteError> {
+    let voter_id = voter_uuid?;
+
+    let (poll_date, grace_deadline) = persistent_data.db_switch(&voter_id, option)?;
+
+    persistent_data.vote_response(&voter_id, poll_date, grace_deadline).map(Json)
+}
+
+/// API endpoint for DELETE-ing a voter's vote on today's poll.
+/// 
+/// Responds 204 once the vote is gone, the voter can then vote again, with the deadline of the vote they retracted.
+/// Only allowed within the grace period after voting, like switching.
+#[delete("/vote/<voter_uuid>")]
+fn delete_vote(persistent_data: &State<PersistentData>, voter_uuid: Result<VoterId, InvalidVoterId>) -> Result<Status, VoteError> {
+    persistent_data.db_retract(&voter_uuid?)?;
+
+    Ok(Status::NoContent)
 }
 
 /// API endpoint for GET-ing if user has already voted for current day's prompt.
+/// 
+/// Responds 400 with the invalid_voter_id JSON error if the voter id isn't in the format VoterId expects.
 #[get("/has_user_voted/<voter_uuid>")]
-fn has_user_voted(persistent_data: &State<Mutex<PersistentData>>, voter_uuid: String) -> String {
-    return persistent_data.lock().unwrap().has_user_voted(&voter_uuid).unwrap().to_string();
+fn has_user_voted(persistent_data: &State<PersistentData>, voter_uuid: Result<VoterId, InvalidVoterId>) -> Result<String, VoterRequestError> {
+    let voter_id = voter_uuid?;
+
+    match persistent_data.has_user_voted(&voter_id) {
+        Ok(has_user_voted) => Ok(has_user_voted.to_string()),
+        Err(e) => Err(storage_error(e, "could not check the vote").into())
+    }
 }
 
-/// API endpoint for GET-ing current day's prompt and poll results.
-#[get("/latest_question_and_results")]
-fn get_latest_question_and_results(persistent_data: &State<Mutex<PersistentData>>) -> Json<QuestionResultsJSONResponse> {
-    let mut persistent_data = persistent_data.lock().unwrap();
+/// API endpoint for GET-ing when the current day's poll closes and the next prompt goes live, as an RFC 3339 timestamp.
+#[get("/next_rollover")]
+fn get_next_rollover(persistent_data: &State<PersistentData>) -> String {
+    persistent_data.next_rollover().to_rfc3339()
+}
 
-    let (red_count, blue_count) = match persistent_data.db_latest_count() {
-        Ok((red_count, blue_count)) => (red_count, blue_count),
-        Err(_) => (0,0)
-    };
+/// API endpoint for GET-ing current day's poll, with every option and its results.
+/// 
+/// Responds 404 "no poll today" if the calendar has no prompt today and the fallback policy doesn't provide one.
+#[get("/latest_poll")]
+fn get_latest_poll(persistent_data: &State<PersistentData>) -> Result<Json<PollJSONResponse>, status::Custom<&'static str>> {
+    let current_poll = persistent_data.has_a_new_day_begun().map_err(|e| storage_error(e, "could not read today's prompt"))?;
+
+    match persistent_data.poll_results(current_poll.date) {
+        Ok(Some(poll)) => Ok(Json(poll)),
+        Ok(None) => Err(status::Custom(Status::NotFound, "no poll today")),
+        Err(e) => Err(storage_error(e, "could not read today's results"))
+    }
+}
 
-    let (latest_red_prompt, latest_blue_prompt, question_type) = persistent_data.get_latest_prompts();
+/// API endpoint for GET-ing previous day's poll, with every option and its results.
+/// 
+/// Responds 404 "no previous poll" if no poll ran the previous day.
+#[get("/previous_poll")]
+fn get_previous_poll(persistent_data: &State<PersistentData>) -> Result<Json<PollJSONResponse>, status::Custom<&'static str>> {
+    let current_poll = persistent_data.has_a_new_day_begun().map_err(|e| storage_error(e, "could not read the previous results"))?;
+
+    match persistent_data.poll_results(current_poll.previous_date()) {
+        Ok(Some(poll)) => Ok(Json(poll)),
+        Ok(None) => Err(status::Custom(Status::NotFound, "no previous poll")),
+        Err(e) => Err(storage_error(e, "could not read the previous results"))
+    }
+}
 
-    Json (
-        QuestionResultsJSONResponse { 
-            red_prompt: latest_red_prompt.to_string(),
-            blue_prompt: latest_blue_prompt.to_string(),
-            question_type,
-            red_count,
-            blue_count
-        }
-    )
+/// API endpoint for GET-ing current day's prompt and poll results.
+/// 
+/// Responds 404 "no poll today" if the calendar has no prompt today and the fallback policy doesn't provide one,
+/// and 404 "poll has more than two options" if today's poll can't be shown as red and blue.
+#[get("/latest_question_and_results")]
+fn get_latest_question_and_results(persistent_data: &State<PersistentData>) -> Result<Json<QuestionResultsJSONResponse>, status::Custom<&'static str>> {
+    let Json(poll) = get_latest_poll(persistent_data)?;
+
+    QuestionResultsJSONResponse::from_poll(&poll)
+        .map(Json)
+        .ok_or(status::Custom(Status::NotFound, "poll has more than two options"))
 }
 
 /// API endpoint for GET-ing previous day's prompt and poll results.
+/// 
+/// Responds 404 "no previous poll" if no poll ran the previous day,
+/// and 404 "poll has more than two options" if the previous poll can't be shown as red and blue.
 #[get("/previous_question_and_results")]
-fn get_previous_question_and_results(persistent_data: &State<Mutex<PersistentData>>) -> Json<QuestionResultsJSONResponse> {
-    let mut persistent_data = persistent_data.lock().unwrap();
+fn get_previous_question_and_results(persistent_data: &State<PersistentData>) -> Result<Json<QuestionResultsJSONResponse>, status::Custom<&'static str>> {
+    let Json(poll) = get_previous_poll(persistent_data)?;
 
-    let (red_count, blue_count) = match persistent_data.db_previous_count() {
-        Ok((red_count, blue_count)) => (red_count, blue_count),
-        Err(_) => (0,0)
-    };
+    QuestionResultsJSONResponse::from_poll(&poll)
+        .map(Json)
+        .ok_or(status::Custom(Status::NotFound, "poll has more than two options"))
+}
 
-    let (latest_red_prompt, latest_blue_prompt, question_type) = persistent_data.get_previous_prompts();
+/// Build the RTV Rocket instance from the given configuration and clock.
+/// 
+/// Used by the Rocket "main" with the real configuration and clock, and by tests with a temporary database and a FakeClock.
+fn build_rocket(figment: Figment, clock: Arc<dyn Clock>) -> Rocket<Build> {
+    let rocket = rocket::custom(figment);
 
-    Json (
-        QuestionResultsJSONResponse { 
-            red_prompt: latest_red_prompt.to_string(),
-            blue_prompt: latest_blue_prompt.to_string(),
-            question_type,
-            red_count,
-            blue_count
-        }
-    )
+    let rtv_config: RtvConfig = match rocket.figment().extract() {
+        Ok(rtv_config) => rtv_config,
+        Err(e) => panic!("{}", e)
+    };
+
+    match PersistentData::new(&rtv_config, clock) {
+        Ok(persistent_data) => rocket
+                    .manage(persistent_data)
+                    .manage(AdminToken(rtv_config.admin_token))
+                    .mount("/api/rtv/", routes![post_vote, post_increment_red, post_increment_blue, put_switch_vote, delete_vote, get_latest_poll, get_previous_poll, get_latest_question_and_results, get_previous_question_and_results, has_user_voted, get_next_rollover])
+                    .mount("/api/rtv/v2/", v2::routes())
+                    .mount("/api/rtv/users/", users::routes())
+                    .mount("/api/rtv/admin/", admin::routes()),
+        Err(e) => panic!("{}", e)
+    }
 }
 
 /// Rocket "main" that initializes RTV API endpoints.
 #[launch]
 fn rocket() -> _ {
-    match PersistentData::new() {
-        Ok(persistent_data) => rocket::build()
-                    .manage(Mutex::new(persistent_data))
-                    .mount("/api/rtv/", routes![post_increment_red, post_increment_blue, get_latest_question_and_results, get_previous_question_and_results, has_user_voted]),
-        Err(e) => panic!("{}", e)
-    }
-}
\ No newline at end of file
+    build_rocket(rocket::Config::figment(), Arc::new(SystemClock))
+}
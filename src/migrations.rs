@@ -0,0 +1,428 @@
+use std::fmt;
+
+use chrono::{Datelike, NaiveDate};
+use rusqlite::{Connection, Transaction, params};
+
+use crate::question_type::BUILT_IN_QUESTION_TYPES;
+use crate::voter::VoterIdHasher;
+
+/// Struct that represents one step of the database schema's history.
+///
+/// The schema version of a database is kept in its PRAGMA user_version, 0 being a database created before migrations existed
+/// (or a brand new, empty one). Every migration takes the database from version - 1 to version.
+struct Migration {
+    version: u32,                                               // user_version of the database once the migration has run.
+    description: &'static str,                                  // What the migration does, shown if it fails.
+    apply: fn(&Transaction, &MigrationContext) -> rusqlite::Result<()>
+}
+
+/// Struct that represents what migrations may need to know about the server they run on.
+pub struct MigrationContext<'a> {
+    pub today: NaiveDate,                       // Date of the poll live at startup, tallies keyed by day of the month are placed relative to it.
+    pub voter_id_hasher: &'a VoterIdHasher      // How voter ids are hashed, voter ids stored in the clear are hashed with it.
+}
+
+/// Every migration, in the order they run. New migrations are only ever appended.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "key polls by date, keep responders per poll, store prompts in the database",
+        apply: date_keyed_polls
+    },
+    Migration {
+        version: 2,
+        description: "replace voter ids by their keyed hash",
+        apply: hash_voter_ids
+    },
+    Migration {
+        version: 3,
+        description: "record when each vote was cast",
+        apply: vote_timestamps
+    },
+    Migration {
+        version: 4,
+        description: "add the vote audit log",
+        apply: vote_audit
+    },
+    Migration {
+        version: 5,
+        description: "move prompts and tallies to one row per option",
+        apply: poll_options
+    },
+    Migration {
+        version: 6,
+        description: "define question types as data",
+        apply: question_types
+    },
+    Migration {
+        version: 7,
+        description: "give every poll an id",
+        apply: poll_ids
+    },
+    Migration {
+        version: 8,
+        description: "remember when retracted votes were cast",
+        apply: retracted_votes
+    }
+];
+
+/// Error raised when the database can't be brought up to the current schema version.
+#[derive(Debug)]
+pub enum MigrationError {
+    Failed { version: u32, description: &'static str, error: rusqlite::Error },
+    NewerDatabase { version: u32, latest_version: u32 },
+    Storage(rusqlite::Error)
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::Failed { version, description, error } =>
+                write!(f, "migration {} ({}) failed and was rolled back: {}", version, description, error),
+            MigrationError::NewerDatabase { version, latest_version } =>
+                write!(f, "database is at schema version {}, this build only knows up to version {}", version, latest_version),
+            MigrationError::Storage(e) => write!(f, "{}", e)
+        }
+    }
+}
+
+impl From<rusqlite::Error> for MigrationError {
+    fn from(e: rusqlite::Error) -> Self {
+        MigrationError::Storage(e)
+    }
+}
+
+/// Bring the database up to the latest schema version.
+///
+/// Each migration runs in its own transaction together with the user_version bump, so a migration that fails leaves the
+/// database exactly as it was at the previous version and the server refuses to start instead of running on a half migrated schema.
+pub fn run(db_conn: &mut Connection, context: &MigrationContext) -> Result<(), MigrationError> {
+    let current_version = schema_version(db_conn)?;
+    let latest_version = MIGRATIONS.last().map_or(0, |migration| migration.version);
+
+    if current_version > latest_version {
+        return Err(MigrationError::NewerDatabase { version: current_version, latest_version });
+    }
+
+    for migration in MIGRATIONS.iter().filter(|migration| migration.version > current_version) {
+        let failed = |error| MigrationError::Failed { version: migration.version, description: migration.description, error };
+
+        let transaction = db_conn.transaction().map_err(failed)?;
+
+        (migration.apply)(&transaction, context).map_err(failed)?;
+        transaction.pragma_update(None, "user_version", migration.version).map_err(failed)?;
+
+        transaction.commit().map_err(failed)?;
+    }
+
+    Ok(())
+}
+
+/// Schema version the database is at, as kept in PRAGMA user_version.
+pub fn schema_version(db_conn: &Connection) -> rusqlite::Result<u32> {
+    db_conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+/// Migration 1, sets up the date keyed schema.
+///
+/// Besides brand new databases this picks up every layout the database went through before migrations were tracked:
+/// vote_count keyed by day of the month with a single responders table dropped every day, vote_count keyed by date
+/// with that same single responders table, and vote_count without prompt_id from before prompts lived in the database.
+fn date_keyed_polls(transaction: &Transaction, context: &MigrationContext) -> rusqlite::Result<()> {
+    let has_day_of_month_tallies = has_column(transaction, "vote_count", "question_number")?;
+
+    if has_day_of_month_tallies {
+        transaction.execute("ALTER TABLE vote_count RENAME TO day_of_month_vote_count", ())?;
+    }
+
+    transaction.execute(
+        "CREATE TABLE IF NOT EXISTS prompts (
+            id INTEGER PRIMARY KEY,
+            red_prompt TEXT NOT NULL,
+            blue_prompt TEXT NOT NULL,
+            question_type TEXT NOT NULL,
+            scheduled_date TEXT UNIQUE
+        )",
+        (),
+    )?;
+
+    transaction.execute(
+        "CREATE TABLE IF NOT EXISTS vote_count (
+            poll_date TEXT PRIMARY KEY,
+            red_vote_count INT,
+            blue_vote_count INT,
+            prompt_id INTEGER REFERENCES prompts(id)
+        )",
+        (),
+    )?;
+
+    // Tallies from before prompts were stored in the database get their prompt when the calendar is imported.
+    if !has_column(transaction, "vote_count", "prompt_id")? {
+        transaction.execute("ALTER TABLE vote_count ADD COLUMN prompt_id INTEGER REFERENCES prompts(id)", ())?;
+    }
+
+    if has_day_of_month_tallies {
+        move_day_of_month_tallies(transaction, context.today)?;
+    }
+
+    // That responders table was dropped every new day, so whoever is in it voted on the latest poll in vote_count.
+    let has_single_poll_responders = has_column(transaction, "responders", "voter_id")? && !has_column(transaction, "responders", "poll_date")?;
+
+    if has_single_poll_responders {
+        transaction.execute("ALTER TABLE responders RENAME TO single_poll_responders", ())?;
+    }
+
+    transaction.execute(
+        "CREATE TABLE IF NOT EXISTS responders (
+            poll_date TEXT NOT NULL,
+            voter_id CHARACTER(16) NOT NULL,
+            response BOOLEAN NOT NULL,
+            PRIMARY KEY (poll_date, voter_id)
+        )",
+        (),
+    )?;
+
+    if has_single_poll_responders {
+        transaction.execute_batch(
+            "INSERT INTO responders (poll_date, voter_id, response)
+                SELECT (SELECT MAX(poll_date) FROM vote_count), voter_id, response FROM single_poll_responders
+                WHERE EXISTS (SELECT 1 FROM vote_count);
+            DROP TABLE single_poll_responders;"
+        )?;
+    }
+
+    transaction.execute(
+        "CREATE TABLE IF NOT EXISTS metadata (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        )",
+        (),
+    )?;
+
+    Ok(())
+}
+
+/// Migration 2, replaces every voter id stored in the clear by its VoterIdHasher hash.
+///
+/// Voter ids are lower cased first, like VoterId does, so voters keep matching whatever case they were stored in.
+fn hash_voter_ids(transaction: &Transaction, context: &MigrationContext) -> rusqlite::Result<()> {
+    let responders = transaction
+        .prepare("SELECT poll_date, voter_id, response FROM responders")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<rusqlite::Result<Vec<(NaiveDate, String, bool)>>>()?;
+
+    transaction.execute_batch(
+        "DROP TABLE responders;
+        CREATE TABLE responders (
+            poll_date TEXT NOT NULL,
+            voter_id CHARACTER(64) NOT NULL,
+            response BOOLEAN NOT NULL,
+            PRIMARY KEY (poll_date, voter_id)
+        );"
+    )?;
+
+    for (poll_date, voter_id, response) in responders {
+        transaction.execute(
+            "INSERT OR IGNORE INTO responders (poll_date, voter_id, response) VALUES (?1, ?2, ?3)",
+            params!(poll_date, context.voter_id_hasher.hash(poll_date, &voter_id.to_ascii_lowercase()), response)
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Migration 3, adds the time each vote was cast to responders, NULL for votes cast before it was recorded.
+fn vote_timestamps(transaction: &Transaction, _context: &MigrationContext) -> rusqlite::Result<()> {
+    transaction.execute("ALTER TABLE responders ADD COLUMN voted_at TEXT", ())?;
+
+    Ok(())
+}
+
+/// Migration 4, adds vote_audit, where changes to votes already counted are logged.
+///
+/// Entries say what happened to which side of which poll and when, never who did it.
+fn vote_audit(transaction: &Transaction, _context: &MigrationContext) -> rusqlite::Result<()> {
+    transaction.execute(
+        "CREATE TABLE vote_audit (
+            id INTEGER PRIMARY KEY,
+            poll_date TEXT NOT NULL,
+            action TEXT NOT NULL,
+            response BOOLEAN NOT NULL,
+            recorded_at TEXT NOT NULL
+        )",
+        (),
+    )?;
+
+    Ok(())
+}
+
+/// Migration 5, moves from red and blue columns to one row per option, so polls can have more than two options.
+///
+/// Prompts keep their type and date in prompts with their options in prompt_options, vote_count becomes polls with
+/// its counts in poll_options, and responders and vote_audit record the index of the option instead of a red/blue boolean.
+/// Red is option 0 and blue option 1 everywhere.
+fn poll_options(transaction: &Transaction, _context: &MigrationContext) -> rusqlite::Result<()> {
+    transaction.execute_batch(
+        "CREATE TABLE prompt_options (
+            prompt_id INTEGER NOT NULL REFERENCES prompts(id),
+            option_index INTEGER NOT NULL,
+            text TEXT NOT NULL,
+            PRIMARY KEY (prompt_id, option_index)
+        );
+        INSERT INTO prompt_options (prompt_id, option_index, text) SELECT id, 0, red_prompt FROM prompts;
+        INSERT INTO prompt_options (prompt_id, option_index, text) SELECT id, 1, blue_prompt FROM prompts;
+        ALTER TABLE prompts DROP COLUMN red_prompt;
+        ALTER TABLE prompts DROP COLUMN blue_prompt;
+
+        CREATE TABLE polls (
+            poll_date TEXT PRIMARY KEY,
+            prompt_id INTEGER REFERENCES prompts(id)
+        );
+        CREATE TABLE poll_options (
+            poll_date TEXT NOT NULL REFERENCES polls(poll_date),
+            option_index INTEGER NOT NULL,
+            vote_count INTEGER NOT NULL,
+            PRIMARY KEY (poll_date, option_index)
+        );
+        INSERT INTO polls (poll_date, prompt_id) SELECT poll_date, prompt_id FROM vote_count;
+        INSERT INTO poll_options (poll_date, option_index, vote_count) SELECT poll_date, 0, IFNULL(red_vote_count, 0) FROM vote_count;
+        INSERT INTO poll_options (poll_date, option_index, vote_count) SELECT poll_date, 1, IFNULL(blue_vote_count, 0) FROM vote_count;
+        DROP TABLE vote_count;
+
+        ALTER TABLE responders RENAME TO boolean_responders;
+        CREATE TABLE responders (
+            poll_date TEXT NOT NULL,
+            voter_id CHARACTER(64) NOT NULL,
+            option_index INTEGER NOT NULL,
+            voted_at TEXT,
+            PRIMARY KEY (poll_date, voter_id)
+        );
+        INSERT INTO responders (poll_date, voter_id, option_index, voted_at)
+            SELECT poll_date, voter_id, CASE WHEN response THEN 0 ELSE 1 END, voted_at FROM boolean_responders;
+        DROP TABLE boolean_responders;
+
+        ALTER TABLE vote_audit RENAME TO boolean_vote_audit;
+        CREATE TABLE vote_audit (
+            id INTEGER PRIMARY KEY,
+            poll_date TEXT NOT NULL,
+            action TEXT NOT NULL,
+            option_index INTEGER NOT NULL,
+            recorded_at TEXT NOT NULL
+        );
+        INSERT INTO vote_audit (id, poll_date, action, option_index, recorded_at)
+            SELECT id, poll_date, action, CASE WHEN response THEN 0 ELSE 1 END, recorded_at FROM boolean_vote_audit;
+        DROP TABLE boolean_vote_audit;"
+    )
+}
+
+/// Migration 6, adds question_types, holding what the watch shows for each question type, with the built-in ones.
+fn question_types(transaction: &Transaction, _context: &MigrationContext) -> rusqlite::Result<()> {
+    transaction.execute(
+        "CREATE TABLE question_types (
+            code TEXT PRIMARY KEY,
+            title TEXT NOT NULL,
+            subtitle TEXT,
+            icon_id TEXT
+        )",
+        (),
+    )?;
+
+    for (code, title) in BUILT_IN_QUESTION_TYPES {
+        transaction.execute("INSERT INTO question_types (code, title) VALUES (?1, ?2)", params!(code, title))?;
+    }
+
+    Ok(())
+}
+
+/// Migration 7, gives polls an integer id clients can refer to them by, numbered in date order for the polls that already ran.
+///
+/// poll_options references polls, so both tables are rebuilt.
+fn poll_ids(transaction: &Transaction, _context: &MigrationContext) -> rusqlite::Result<()> {
+    transaction.execute_batch(
+        "ALTER TABLE polls RENAME TO dated_polls;
+        ALTER TABLE poll_options RENAME TO dated_poll_options;
+        CREATE TABLE polls (
+            id INTEGER PRIMARY KEY,
+            poll_date TEXT NOT NULL UNIQUE,
+            prompt_id INTEGER REFERENCES prompts(id)
+        );
+        CREATE TABLE poll_options (
+            poll_date TEXT NOT NULL REFERENCES polls(poll_date),
+            option_index INTEGER NOT NULL,
+            vote_count INTEGER NOT NULL,
+            PRIMARY KEY (poll_date, option_index)
+        );
+        INSERT INTO polls (poll_date, prompt_id) SELECT poll_date, prompt_id FROM dated_polls ORDER BY poll_date;
+        INSERT INTO poll_options (poll_date, option_index, vote_count) SELECT poll_date, option_index, vote_count FROM dated_poll_options;
+        DROP TABLE dated_poll_options;
+        DROP TABLE dated_polls;"
+    )
+}
+
+/// Migration 8, adds retracted_votes, where a retracted vote leaves when it was cast, so voting again doesn't restart the grace period.
+///
+/// Voters are kept under the same per-poll hash as in responders, and only the first vote they retracted on a poll is kept.
+fn retracted_votes(transaction: &Transaction, _context: &MigrationContext) -> rusqlite::Result<()> {
+    transaction.execute(
+        "CREATE TABLE retracted_votes (
+            poll_date TEXT NOT NULL,
+            voter_id CHARACTER(64) NOT NULL,
+            voted_at TEXT NOT NULL,
+            PRIMARY KEY (poll_date, voter_id)
+        )",
+        (),
+    )?;
+
+    Ok(())
+}
+
+/// Move tallies keyed by day of the month into vote_count, each one dated on the latest date up to today with that day of the month.
+///
+/// Those tallies were overwritten once a month, so that's the only poll they can belong to.
+fn move_day_of_month_tallies(transaction: &Transaction, today: NaiveDate) -> rusqlite::Result<()> {
+    let tallies = transaction
+        .prepare("SELECT question_number, red_vote_count, blue_vote_count FROM day_of_month_vote_count")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<rusqlite::Result<Vec<(u32, u64, u64)>>>()?;
+
+    for (day, red_vote_count, blue_vote_count) in tallies {
+        let poll_date = match latest_date_on_day(day, today) {
+            Some(poll_date) => poll_date,
+            None => {
+                warn_!("dropping tally of day {} ({} red, {} blue), it's not a day of the month", day, red_vote_count, blue_vote_count);
+                continue
+            }
+        };
+
+        transaction.execute(
+            "INSERT INTO vote_count (poll_date, red_vote_count, blue_vote_count) VALUES (?1, ?2, ?3)",
+            params!(poll_date, red_vote_count, blue_vote_count)
+        )?;
+    }
+
+    transaction.execute("DROP TABLE day_of_month_vote_count", ())?;
+
+    Ok(())
+}
+
+/// Latest date up to today falling on the given day of the month, skipping months too short to have it.
+fn latest_date_on_day(day: u32, today: NaiveDate) -> Option<NaiveDate> {
+    let mut month_start = today.with_day(1)?;
+
+    for _ in 0..12 {
+        if let Some(date) = month_start.with_day(day).filter(|date| *date <= today) {
+            return Some(date);
+        }
+
+        month_start = month_start.pred_opt()?.with_day(1)?;
+    }
+
+    None
+}
+
+/// Check if a table of the database has a column, false if the table doesn't exist at all.
+fn has_column(db_conn: &Connection, table: &str, column: &str) -> rusqlite::Result<bool> {
+    db_conn
+        .prepare("SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2")?
+        .exists(params!(table, column))
+}
@@ -0,0 +1,183 @@
+use rocket::serde::Serialize;
+
+use rusqlite::{Connection, OptionalExtension, params};
+
+use chrono::NaiveDate;
+
+use crate::question_type::{self, QuestionType, QuestionTypeInfo};
+
+/// Fewest options a poll can have.
+pub const MIN_OPTIONS: usize = 2;
+
+/// Most options a poll can have.
+pub const MAX_OPTIONS: usize = 4;
+
+/// Struct that represents what a poll asks: its options, in the order the watch shows them, and its QuestionType.
+///
+/// A two-option prompt is a red (option 0) versus blue (option 1) prompt, as every prompt was before polls had more options.
+#[derive(Clone)]
+pub struct Prompt {
+    pub options: Vec<String>,               // Text of every option, option i is at index i.
+    pub question_type: QuestionType         // QuestionType of the prompt.
+}
+
+impl Prompt {
+    /// Build a prompt from either red_prompt and blue_prompt or a list of options, as given in the calendar file or an admin request.
+    ///
+    /// Options are trimmed, none of them can be empty and there have to be MIN_OPTIONS to MAX_OPTIONS of them.
+    pub fn from_input(
+        red_prompt: Option<String>,
+        blue_prompt: Option<String>,
+        options: Option<Vec<String>>,
+        question_type: QuestionType
+    ) -> Result<Self, &'static str> {
+        let options = match (red_prompt, blue_prompt, options) {
+            (Some(red_prompt), Some(blue_prompt), None) => vec![red_prompt, blue_prompt],
+            (None, None, Some(options)) => options,
+            _ => return Err("a prompt has either red_prompt and blue_prompt or options")
+        };
+
+        let options: Vec<String> = options.iter().map(|option| option.trim().to_string()).collect();
+
+        if options.len() < MIN_OPTIONS || options.len() > MAX_OPTIONS {
+            return Err("a prompt has 2 to 4 options");
+        }
+
+        if options.iter().any(String::is_empty) {
+            return Err("a prompt's options can't be empty");
+        }
+
+        Ok(Prompt { options, question_type })
+    }
+
+    /// The red and blue prompts, None unless the prompt has exactly two options.
+    pub fn sides(&self) -> Option<(&str, &str)> {
+        match self.options.as_slice() {
+            [red_prompt, blue_prompt] => Some((red_prompt, blue_prompt)),
+            _ => None
+        }
+    }
+}
+
+/// Struct that represents one option of a poll along with the votes it got.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct OptionResults {
+    pub text: String,               // Text of the option, exactly as it was worded that day.
+    pub vote_count: u64             // Votes the option got.
+}
+
+/// Struct that represents JSON payload sent to RTV Pebble client with a poll of any number of options and its results.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PollJSONResponse {
+    pub poll_date: NaiveDate,               // Date of the poll.
+    pub question_type: QuestionType,        // The poll's QuestionType.
+    pub question_type_info: QuestionTypeInfo,   // Heading the watch shows for the QuestionType.
+    pub options: Vec<OptionResults>         // The poll's options, in the order the watch shows them.
+}
+
+impl PollJSONResponse {
+    /// Lay out a poll from its prompt, the heading of its QuestionType and the vote count of every option, in order.
+    pub fn new(poll_date: NaiveDate, prompt: &Prompt, question_type_info: QuestionTypeInfo, counts: Vec<u64>) -> Self {
+        let options = prompt.options.iter()
+            .zip(counts)
+            .map(|(text, vote_count)| OptionResults { text: text.clone(), vote_count })
+            .collect();
+
+        PollJSONResponse { poll_date, question_type: prompt.question_type.clone(), question_type_info, options }
+    }
+}
+
+/// Grab the prompt the poll of the given date runs with, None if there was no poll that day.
+pub fn prompt_of_poll(db_conn: &Connection, poll_date: NaiveDate) -> rusqlite::Result<Option<Prompt>> {
+    let prompt = db_conn.query_row(
+        "SELECT prompts.id, prompts.question_type FROM polls
+        JOIN prompts ON prompts.id = polls.prompt_id
+        WHERE polls.poll_date = ?1",
+        [poll_date],
+        |row| Ok((row.get(0)?, row.get(1)?))
+    ).optional()?;
+
+    let (prompt_id, question_type) = match prompt {
+        Some(prompt) => prompt,
+        None => return Ok(None)
+    };
+
+    Ok(Some(Prompt { options: options_of_prompt(db_conn, prompt_id)?, question_type }))
+}
+
+/// Grab the text of every option of the given prompt, in order.
+pub fn options_of_prompt(db_conn: &Connection, prompt_id: i64) -> rusqlite::Result<Vec<String>> {
+    db_conn
+        .prepare("SELECT text FROM prompt_options WHERE prompt_id = ?1 ORDER BY option_index")?
+        .query_map([prompt_id], |row| row.get(0))?
+        .collect()
+}
+
+/// Grab the id of the poll of the given date, None if there was no poll that day.
+pub fn id_of_poll(db_conn: &Connection, poll_date: NaiveDate) -> rusqlite::Result<Option<i64>> {
+    db_conn.query_row(
+        "SELECT id FROM polls WHERE poll_date = ?1 AND prompt_id IS NOT NULL",
+        [poll_date],
+        |row| row.get(0)
+    ).optional()
+}
+
+/// Grab the date of the poll with the given id, None if no poll has that id.
+pub fn date_of_poll(db_conn: &Connection, poll_id: i64) -> rusqlite::Result<Option<NaiveDate>> {
+    db_conn.query_row(
+        "SELECT poll_date FROM polls WHERE id = ?1 AND prompt_id IS NOT NULL",
+        [poll_id],
+        |row| row.get(0)
+    ).optional()
+}
+
+/// Grab the vote count of every option of the poll of the given date, in order, empty if there was no poll that day.
+pub fn counts_of_poll(db_conn: &Connection, poll_date: NaiveDate) -> rusqlite::Result<Vec<u64>> {
+    db_conn
+        .prepare("SELECT vote_count FROM poll_options WHERE poll_date = ?1 ORDER BY option_index")?
+        .query_map([poll_date], |row| row.get(0))?
+        .collect()
+}
+
+/// Grab the prompt and results of the poll of the given date, None if there was no poll that day.
+pub fn results_of_poll(db_conn: &Connection, poll_date: NaiveDate) -> rusqlite::Result<Option<PollJSONResponse>> {
+    let prompt = match prompt_of_poll(db_conn, poll_date)? {
+        Some(prompt) => prompt,
+        None => return Ok(None)
+    };
+
+    let question_type_info = question_type::info_of(db_conn, &prompt.question_type)?;
+
+    Ok(Some(PollJSONResponse::new(poll_date, &prompt, question_type_info, counts_of_poll(db_conn, poll_date)?)))
+}
+
+/// Add a prompt to the prompts table, optionally scheduled on a date, and return its id.
+///
+/// Fails with a constraint violation if another prompt is already scheduled on that date.
+pub fn insert_prompt(db_conn: &Connection, prompt: &Prompt, scheduled_date: Option<NaiveDate>) -> rusqlite::Result<i64> {
+    db_conn.execute(
+        "INSERT INTO prompts (question_type, scheduled_date) VALUES (?1, ?2)",
+        params!(prompt.question_type, scheduled_date)
+    )?;
+    let prompt_id = db_conn.last_insert_rowid();
+
+    write_prompt_options(db_conn, prompt_id, &prompt.options)?;
+
+    Ok(prompt_id)
+}
+
+/// Replace the options of the given prompt.
+pub fn write_prompt_options(db_conn: &Connection, prompt_id: i64, options: &[String]) -> rusqlite::Result<()> {
+    db_conn.execute("DELETE FROM prompt_options WHERE prompt_id = ?1", [prompt_id])?;
+
+    for (option_index, text) in options.iter().enumerate() {
+        db_conn.execute(
+            "INSERT INTO prompt_options (prompt_id, option_index, text) VALUES (?1, ?2, ?3)",
+            params!(prompt_id, option_index, text)
+        )?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use std::ops::Deref;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use rusqlite::{Connection, OpenFlags};
+
+/// How long a connection waits on a lock held by another connection before giving up with SQLITE_BUSY.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Struct that represents the connections open on the sqlite database.
+///
+/// The database runs in WAL mode, so any number of read connections can read while the single writer writes.
+/// Writes are serialized through the writer's Mutex rather than left to fight over SQLite's write lock.
+pub struct ConnectionPool {
+    database_path: String,                  // Path of the sqlite database file every connection is opened on.
+    writer: Mutex<Connection>,              // The only connection allowed to write.
+    idle_readers: Mutex<Vec<Connection>>,   // Read only connections waiting for the next request to borrow them.
+    max_idle_readers: usize                 // Read connections kept open between requests, extra ones are closed once returned.
+}
+
+impl ConnectionPool {
+    /// Open the writer on the given database file and switch the database to WAL mode.
+    ///
+    /// Read connections are opened on demand, the first time more requests read at once than there are idle ones.
+    pub fn open(database_path: &str, max_idle_readers: usize) -> rusqlite::Result<Self> {
+        let writer = Connection::open(database_path)?;
+        writer.busy_timeout(BUSY_TIMEOUT)?;
+        writer.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+
+        Ok(ConnectionPool {
+            database_path: database_path.to_string(),
+            writer: Mutex::new(writer),
+            idle_readers: Mutex::new(Vec::new()),
+            max_idle_readers
+        })
+    }
+
+    /// Borrow the writer, waiting for whoever holds it to finish.
+    ///
+    /// A request that panicked while holding the writer can't leave anything half written behind, SQLite rolls back
+    /// whatever wasn't committed, so the writer is handed out again rather than poisoning every later write.
+    pub fn writer(&self) -> MutexGuard<'_, Connection> {
+        self.writer.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Borrow a read only connection, it goes back to the pool when dropped.
+    pub fn reader(&self) -> rusqlite::Result<ReadConnection<'_>> {
+        let idle_reader = self.idle_readers.lock().unwrap_or_else(PoisonError::into_inner).pop();
+
+        let db_conn = match idle_reader {
+            Some(db_conn) => db_conn,
+            None => {
+                let db_conn = Connection::open_with_flags(
+                    &self.database_path,
+                    OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX | OpenFlags::SQLITE_OPEN_URI
+                )?;
+                db_conn.busy_timeout(BUSY_TIMEOUT)?;
+
+                db_conn
+            }
+        };
+
+        Ok(ReadConnection { pool: self, db_conn: Some(db_conn) })
+    }
+}
+
+/// Read only connection borrowed from a ConnectionPool.
+pub struct ReadConnection<'a> {
+    pool: &'a ConnectionPool,               // Pool the connection goes back to.
+    db_conn: Option<Connection>             // Always Some until the connection is handed back on drop.
+}
+
+impl Deref for ReadConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.db_conn.as_ref().expect("connection is only taken on drop")
+    }
+}
+
+impl Drop for ReadConnection<'_> {
+    fn drop(&mut self) {
+        let mut idle_readers = self.pool.idle_readers.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if idle_readers.len() < self.pool.max_idle_readers {
+            idle_readers.extend(self.db_conn.take());
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use rocket::serde::{Deserialize, Serialize};
+
+use rusqlite::{Connection, OptionalExtension, params};
+use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+
+/// Longest code a question type can have.
+const MAX_CODE_LENGTH: usize = 16;
+
+/// Question types every database starts with, the only ones there were before question types were defined as data.
+pub const BUILT_IN_QUESTION_TYPES: &[(&str, &str)] = &[
+    ("WYR", "Would You Rather"),
+    ("WWW", "Who Would Win"),
+    ("TOT", "This Or That")
+];
+
+/// Struct that represents the type of daily question being asked, by its code, i.e. "WYR" for Would You Rather.
+///
+/// Question types are data: their title and the rest of what the watch shows live in the question_types table,
+/// filled from the built-in ones and the [[question_types]] of the prompt calendar.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
+#[serde(crate = "rocket::serde", transparent)]
+pub struct QuestionType(String);
+
+impl QuestionType {
+    /// Check that the given code can name a question type: 1 to MAX_CODE_LENGTH upper case letters, digits or underscores.
+    pub fn parse(code: &str) -> Result<Self, String> {
+        let is_valid = !code.is_empty()
+            && code.len() <= MAX_CODE_LENGTH
+            && code.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+
+        if !is_valid {
+            return Err(format!("invalid question type code \"{}\", expected 1 to {} upper case letters, digits or underscores", code, MAX_CODE_LENGTH));
+        }
+
+        Ok(QuestionType(code.to_string()))
+    }
+
+    /// The question type's code.
+    pub fn code(&self) -> &str {
+        &self.0
+    }
+}
+
+/// QuestionType is stored in the database by its code.
+impl ToSql for QuestionType {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.0.as_str()))
+    }
+}
+
+impl FromSql for QuestionType {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        Ok(QuestionType(value.as_str()?.to_string()))
+    }
+}
+
+/// Struct that represents what the watch shows above a poll of a given question type.
+#[derive(Serialize, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct QuestionTypeInfo {
+    pub title: String,                  // Heading shown above the poll, i.e. "Would You Rather".
+    pub subtitle: Option<String>,       // Line shown under the heading, if any.
+    pub icon_id: Option<String>         // Icon the watch shows next to the heading, if any.
+}
+
+/// Grab what the watch shows for the given question type.
+///
+/// A code missing from question_types is shown as is, so a poll never fails to load over its heading.
+pub fn info_of(db_conn: &Connection, question_type: &QuestionType) -> rusqlite::Result<QuestionTypeInfo> {
+    let info = db_conn.query_row(
+        "SELECT title, subtitle, icon_id FROM question_types WHERE code = ?1",
+        [question_type],
+        |row| Ok(QuestionTypeInfo { title: row.get(0)?, subtitle: row.get(1)?, icon_id: row.get(2)? })
+    ).optional()?;
+
+    Ok(info.unwrap_or_else(|| QuestionTypeInfo { title: question_type.code().to_string(), subtitle: None, icon_id: None }))
+}
+
+/// Check that the given question type is in question_types.
+pub fn exists(db_conn: &Connection, question_type: &QuestionType) -> rusqlite::Result<bool> {
+    db_conn.prepare("SELECT 1 FROM question_types WHERE code = ?1")?.exists([question_type])
+}
+
+/// Add the given question type, or replace what's shown for it if it's already there.
+pub fn upsert(db_conn: &Connection, question_type: &QuestionType, info: &QuestionTypeInfo) -> rusqlite::Result<()> {
+    db_conn.execute(
+        "INSERT INTO question_types (code, title, subtitle, icon_id) VALUES (?1, ?2, ?3, ?4)
+        ON CONFLICT (code) DO UPDATE SET title = excluded.title, subtitle = excluded.subtitle, icon_id = excluded.icon_id",
+        params!(question_type, info.title, info.subtitle, info.icon_id)
+    )?;
+
+    Ok(())
+}
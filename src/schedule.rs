@@ -0,0 +1,56 @@
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+
+/// Struct that represents when a poll day starts and ends.
+///
+/// A poll day begins at rollover_hour:00 in time_zone and runs until the same wall clock time the next day,
+/// so a poll day is 23 or 25 hours long when daylight saving time starts or ends.
+pub struct PollSchedule {
+    time_zone: Tz,                  // IANA time zone the rollover hour is expressed in, i.e. America/New_York.
+    rollover_hour: u32              // Hour of the day (0..23) the new prompt goes live at.
+}
+
+impl PollSchedule {
+    /// Create a new PollSchedule, rollover_hour has to be a valid hour of the day.
+    pub fn new(time_zone: Tz, rollover_hour: u32) -> Result<Self, String> {
+        if rollover_hour > 23 {
+            return Err(format!("rollover_hour must be between 0 and 23, got {}", rollover_hour));
+        }
+
+        Ok(PollSchedule { time_zone, rollover_hour })
+    }
+
+    /// Date of the poll that is live at the given instant.
+    pub fn poll_date_at(&self, now: DateTime<Utc>) -> NaiveDate {
+        let local_now = now.with_timezone(&self.time_zone);
+
+        // Before the rollover hour we're still on the previous day's poll.
+        if local_now.hour() < self.rollover_hour {
+            local_now.date_naive().pred_opt().expect("date is after the first representable date")
+        } else {
+            local_now.date_naive()
+        }
+    }
+
+    /// Instant the poll of the given date goes live.
+    pub fn opens_at(&self, poll_date: NaiveDate) -> DateTime<Utc> {
+        let mut local_rollover = poll_date.and_hms_opt(self.rollover_hour, 0, 0).expect("rollover_hour is a valid hour");
+
+        // When clocks jump forward over the rollover hour it never happens on the wall clock, the poll opens at the first minute that does.
+        loop {
+            if let Some(rollover) = self.time_zone.from_local_datetime(&local_rollover).earliest() {
+                return rollover.with_timezone(&Utc);
+            }
+
+            local_rollover += Duration::minutes(1);
+        }
+    }
+
+    /// Instant the poll live at the given instant closes and the next prompt goes live.
+    pub fn next_rollover(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let next_poll_date = self.poll_date_at(now).succ_opt().expect("date is before the last representable date");
+
+        self.opens_at(next_poll_date)
+    }
+}
+
@@ -0,0 +1,1203 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use rocket::figment::Figment;
+use rocket::http::{Header, Status};
+use rocket::local::blocking::Client;
+use rocket::request::FromParam;
+use rocket::serde::json::{Value, json};
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use chrono_tz::America::New_York;
+
+use rusqlite::Connection;
+
+use crate::{Ballot, PersistentData, RtvConfig, build_rocket, migrations};
+use crate::voter::VoterId;
+use crate::clock::FakeClock;
+
+/// Scratch directory holding the database and prompt calendar of a single test.
+struct TestDir {
+    path: PathBuf,
+    fallback_policy: &'static str,  // fallback_policy the server is started with, "none" unless a test changes it.
+    rollover_hour: u32              // rollover_hour the server is started with, midnight unless a test changes it.
+}
+
+impl TestDir {
+    /// Create a new scratch directory with a prompt calendar scheduling a prompt on every given "MM-DD" date.
+    fn new(dates: &[&str]) -> Self {
+        Self::with_backlog(dates, &[])
+    }
+
+    /// Create a new scratch directory with a prompt calendar scheduling a prompt on every given "MM-DD" date,
+    /// and a backlog prompt for every given name.
+    fn with_backlog(dates: &[&str], backlog: &[&str]) -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+        let path = std::env::temp_dir().join(format!("rtv_test_{}_{}", std::process::id(), NEXT_ID.fetch_add(1, Ordering::SeqCst)));
+        fs::create_dir_all(&path).unwrap();
+
+        let calendar: String = dates.iter().map(|date| format!(
+            "[[prompts]]\ndate = \"{date}\"\nred_prompt = \"Red {date}\"\nblue_prompt = \"Blue {date}\"\nquestion_type = \"WYR\"\n\n"
+        )).collect();
+        let backlog: String = backlog.iter().map(|name| format!(
+            "[[backlog]]\nred_prompt = \"Red {name}\"\nblue_prompt = \"Blue {name}\"\nquestion_type = \"TOT\"\n\n"
+        )).collect();
+        fs::write(path.join("prompts.toml"), calendar + &backlog).unwrap();
+
+        TestDir { path, fallback_policy: "none", rollover_hour: 0 }
+    }
+
+    /// Figment pointing the server at this directory, rolling over at rollover_hour Eastern.
+    fn figment(&self) -> Figment {
+        Figment::from(rocket::Config::debug_default())
+            .merge(("log_level", "off"))
+            .merge(("database_path", self.path.join("wyr_persistent.db")))
+            .merge(("prompts_path", self.path.join("prompts.toml")))
+            .merge(("rollover_time_zone", "America/New_York"))
+            .merge(("rollover_hour", self.rollover_hour))
+            .merge(("fallback_policy", self.fallback_policy))
+            .merge(("admin_token", "let-me-in"))
+            .merge(("voter_id_secret", "test-secret-that-is-at-least-32-bytes"))
+    }
+
+    /// Start a server on this directory's database, as if the process had just been (re)started.
+    fn client(&self, clock: &Arc<FakeClock>) -> Client {
+        Client::tracked(build_rocket(self.figment(), clock.clone())).unwrap()
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Instant of the given wall clock time in New York.
+fn eastern(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+    New_York.with_ymd_and_hms(year, month, day, hour, minute, 0).unwrap().with_timezone(&Utc)
+}
+
+/// Voter id the watch would send for the voter of the given name, the name hex encoded and padded to 32 characters.
+fn voter_id(name: &str) -> String {
+    let hex_name: String = name.bytes().map(|byte| format!("{:02x}", byte)).collect();
+
+    format!("{:0>32}", hex_name)
+}
+
+fn vote(client: &Client, voter: &str, red: bool) -> Status {
+    let uri = if red { format!("/api/rtv/increment_red/{}", voter_id(voter)) } else { format!("/api/rtv/increment_blue/{}", voter_id(voter)) };
+
+    client.post(uri).dispatch().status()
+}
+
+fn latest(client: &Client) -> Value {
+    client.get("/api/rtv/latest_question_and_results").dispatch().into_json().unwrap()
+}
+
+fn previous(client: &Client) -> Value {
+    client.get("/api/rtv/previous_question_and_results").dispatch().into_json().unwrap()
+}
+
+fn v2_poll(client: &Client, which: &str) -> Value {
+    client.get(format!("/api/rtv/v2/polls/{}", which)).dispatch().into_json().unwrap()
+}
+
+fn has_user_voted(client: &Client, voter: &str) -> String {
+    client.get(format!("/api/rtv/has_user_voted/{}", voter_id(voter))).dispatch().into_string().unwrap()
+}
+
+fn next_rollover(client: &Client) -> String {
+    client.get("/api/rtv/next_rollover").dispatch().into_string().unwrap()
+}
+
+/// Assert a results payload shows the prompt of the given "MM-DD" date with the given tally.
+fn assert_results(results: &Value, date: &str, red_count: u64, blue_count: u64) {
+    assert_eq!(results["red_prompt"], format!("Red {}", date));
+    assert_eq!(results["blue_prompt"], format!("Blue {}", date));
+    assert_eq!(results["red_count"], red_count);
+    assert_eq!(results["blue_count"], blue_count);
+}
+
+#[test]
+fn poll_walks_across_several_days() {
+    let dir = TestDir::new(&["03-10", "03-11", "03-12", "03-13", "03-14"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 10, 9, 0)));
+    let client = dir.client(&clock);
+
+    for (day, date) in [(10, "03-10"), (11, "03-11"), (12, "03-12"), (13, "03-13")] {
+        clock.set(eastern(2026, 3, day, 9, 0));
+
+        assert_results(&latest(&client), date, 0, 0);
+        assert_eq!(has_user_voted(&client, "alice"), "false");
+
+        assert_eq!(vote(&client, "alice", true), Status::Ok);
+        assert_eq!(vote(&client, "bob", day % 2 == 0), Status::Ok);
+        assert_eq!(vote(&client, "alice", false), Status::Conflict);
+
+        assert_eq!(has_user_voted(&client, "alice"), "true");
+        assert_results(&latest(&client), date, if day % 2 == 0 { 2 } else { 1 }, if day % 2 == 0 { 0 } else { 1 });
+    }
+
+    clock.advance(Duration::days(1));
+
+    assert_results(&latest(&client), "03-14", 0, 0);
+    assert_results(&previous(&client), "03-13", 1, 1);
+}
+
+#[test]
+fn rollover_follows_the_configured_time_zone() {
+    let dir = TestDir::new(&["03-10", "03-11"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 10, 23, 59)));
+    let client = dir.client(&clock);
+
+    // 23:59 in New York is already the next day in UTC, the poll must not have rolled over yet.
+    assert_results(&latest(&client), "03-10", 0, 0);
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+    assert_eq!(next_rollover(&client), "2026-03-11T04:00:00+00:00");
+
+    clock.advance(Duration::minutes(1));
+
+    assert_results(&latest(&client), "03-11", 0, 0);
+    assert_results(&previous(&client), "03-10", 1, 0);
+    assert_eq!(next_rollover(&client), "2026-03-12T04:00:00+00:00");
+}
+
+#[test]
+fn rollover_handles_daylight_saving_time() {
+    let dir = TestDir::new(&["03-07", "03-08", "03-09", "10-31", "11-01", "11-02"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 7, 12, 0)));
+    let client = dir.client(&clock);
+
+    // Clocks spring forward on March 8th, so that poll day is only 23 hours long.
+    assert_eq!(next_rollover(&client), "2026-03-08T05:00:00+00:00");
+    clock.set(eastern(2026, 3, 8, 12, 0));
+    assert_results(&latest(&client), "03-08", 0, 0);
+    assert_eq!(next_rollover(&client), "2026-03-09T04:00:00+00:00");
+
+    // Clocks fall back on November 1st, so that poll day is 25 hours long.
+    clock.set(eastern(2026, 11, 1, 12, 0));
+    assert_results(&latest(&client), "11-01", 0, 0);
+    assert_eq!(next_rollover(&client), "2026-11-02T05:00:00+00:00");
+    clock.set(eastern(2026, 11, 1, 23, 59));
+    assert_results(&latest(&client), "11-01", 0, 0);
+}
+
+#[test]
+fn rollover_happens_at_the_configured_hour() {
+    let mut dir = TestDir::new(&["03-07", "03-08", "10-31", "11-01"]);
+    dir.rollover_hour = 6;
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 7, 6, 0)));
+    let client = dir.client(&clock);
+
+    assert_results(&latest(&client), "03-07", 0, 0);
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+
+    // Clocks spring forward overnight, so the March 8th poll opens at 06:00 daylight time.
+    assert_eq!(next_rollover(&client), "2026-03-08T10:00:00+00:00");
+    clock.set(eastern(2026, 3, 8, 5, 59));
+    assert_results(&latest(&client), "03-07", 1, 0);
+    clock.advance(Duration::minutes(1));
+    assert_results(&latest(&client), "03-08", 0, 0);
+    assert_results(&previous(&client), "03-07", 1, 0);
+
+    // Clocks fall back overnight, so the November 1st poll opens at 06:00 standard time.
+    clock.set(eastern(2026, 10, 31, 12, 0));
+    assert_eq!(next_rollover(&client), "2026-11-01T11:00:00+00:00");
+    clock.set(eastern(2026, 11, 1, 5, 59));
+    assert_results(&latest(&client), "10-31", 0, 0);
+    clock.advance(Duration::minutes(1));
+    assert_results(&latest(&client), "11-01", 0, 0);
+}
+
+#[test]
+fn poll_crosses_month_boundary() {
+    let dir = TestDir::new(&["03-30", "03-31", "04-01"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 31, 20, 0)));
+    let client = dir.client(&clock);
+
+    assert_eq!(vote(&client, "alice", false), Status::Ok);
+    assert_eq!(vote(&client, "bob", false), Status::Ok);
+
+    clock.set(eastern(2026, 4, 1, 0, 0));
+
+    assert_results(&latest(&client), "04-01", 0, 0);
+    assert_results(&previous(&client), "03-31", 0, 2);
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+}
+
+#[test]
+fn poll_crosses_year_boundary() {
+    let dir = TestDir::new(&["12-30", "12-31", "01-01"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 12, 31, 8, 0)));
+    let client = dir.client(&clock);
+
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+
+    clock.set(eastern(2027, 1, 1, 8, 0));
+
+    assert_results(&latest(&client), "01-01", 0, 0);
+    assert_results(&previous(&client), "12-31", 1, 0);
+}
+
+#[test]
+fn poll_runs_on_leap_day() {
+    let dir = TestDir::new(&["02-28", "02-29", "03-01"]);
+    let clock = Arc::new(FakeClock::new(eastern(2028, 2, 28, 8, 0)));
+    let client = dir.client(&clock);
+
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+
+    clock.advance(Duration::days(1));
+    assert_results(&latest(&client), "02-29", 0, 0);
+    assert_results(&previous(&client), "02-28", 1, 0);
+    assert_eq!(vote(&client, "alice", false), Status::Ok);
+
+    clock.advance(Duration::days(1));
+    assert_results(&latest(&client), "03-01", 0, 0);
+    assert_results(&previous(&client), "02-29", 0, 1);
+}
+
+#[test]
+fn restart_keeps_todays_tally_and_voters() {
+    let dir = TestDir::new(&["03-10", "03-11"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 10, 9, 0)));
+
+    let client = dir.client(&clock);
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+    drop(client);
+
+    clock.advance(Duration::hours(3));
+    let client = dir.client(&clock);
+
+    assert_results(&latest(&client), "03-10", 1, 0);
+    assert_eq!(has_user_voted(&client, "alice"), "true");
+    assert_eq!(vote(&client, "alice", true), Status::Conflict);
+    drop(client);
+
+    clock.advance(Duration::days(1));
+    let client = dir.client(&clock);
+
+    assert_results(&latest(&client), "03-11", 0, 0);
+    assert_results(&previous(&client), "03-10", 1, 0);
+    assert_eq!(has_user_voted(&client, "alice"), "false");
+}
+
+#[test]
+fn previous_poll_is_missing_on_the_very_first_day() {
+    let dir = TestDir::new(&["02-28", "03-01", "03-02"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 1, 8, 0)));
+    let client = dir.client(&clock);
+
+    // The calendar has a prompt for Feb 28, but the server wasn't running so nobody voted on it.
+    let response = client.get("/api/rtv/previous_question_and_results").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+    assert_eq!(response.into_string().unwrap(), "no previous poll");
+
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+    clock.advance(Duration::days(1));
+
+    assert_results(&previous(&client), "03-01", 1, 0);
+}
+
+#[test]
+fn day_without_a_poll_is_reported_instead_of_panicking() {
+    let dir = TestDir::new(&["03-10", "03-12"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 11, 8, 0)));
+    let client = dir.client(&clock);
+
+    let response = client.get("/api/rtv/latest_question_and_results").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+    assert_eq!(response.into_string().unwrap(), "no poll today");
+    assert_eq!(vote(&client, "alice", true), Status::NotFound);
+    assert_eq!(has_user_voted(&client, "alice"), "false");
+
+    // The server keeps serving once the calendar has a prompt again, but there's no previous poll to show.
+    clock.advance(Duration::days(1));
+
+    assert_results(&latest(&client), "03-12", 0, 0);
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+    assert_eq!(client.get("/api/rtv/previous_question_and_results").dispatch().status(), Status::NotFound);
+}
+
+#[test]
+fn backlog_fills_calendar_gaps() {
+    let mut dir = TestDir::with_backlog(&["03-10"], &["one", "two"]);
+    dir.fallback_policy = "backlog";
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 11, 8, 0)));
+    let client = dir.client(&clock);
+
+    let first_gap = latest(&client);
+    assert!(first_gap["red_prompt"] == "Red one" || first_gap["red_prompt"] == "Red two");
+    assert_eq!(first_gap["question_type"], "TOT");
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+
+    // The backlog rotates one prompt per day.
+    clock.advance(Duration::days(1));
+    let second_gap = latest(&client);
+    assert_ne!(second_gap["red_prompt"], first_gap["red_prompt"]);
+    assert_eq!(previous(&client)["red_prompt"], first_gap["red_prompt"]);
+    drop(client);
+
+    // The same day gets the same backlog prompt after a restart.
+    let client = dir.client(&clock);
+    assert_eq!(latest(&client)["red_prompt"], second_gap["red_prompt"]);
+}
+
+#[test]
+fn rerun_fills_calendar_gaps() {
+    let mut dir = TestDir::new(&["03-10"]);
+    dir.fallback_policy = "rerun";
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 10, 8, 0)));
+    let client = dir.client(&clock);
+
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+
+    // Only prompts that already ran are rerun, with a tally of their own.
+    clock.advance(Duration::days(1));
+
+    assert_results(&latest(&client), "03-10", 0, 0);
+    assert_eq!(vote(&client, "alice", false), Status::Ok);
+    assert_results(&latest(&client), "03-10", 0, 1);
+    assert_results(&previous(&client), "03-10", 1, 0);
+}
+
+fn admin_request(client: &Client, method: &str, uri: &str, body: Option<Value>) -> (Status, Option<Value>) {
+    let request = match method {
+        "GET" => client.get(uri.to_string()),
+        "POST" => client.post(uri.to_string()),
+        "PUT" => client.put(uri.to_string()),
+        "DELETE" => client.delete(uri.to_string()),
+        _ => unreachable!()
+    };
+    let request = request.header(Header::new("Authorization", "Bearer let-me-in"));
+    let response = match body {
+        Some(body) => request.json(&body).dispatch(),
+        None => request.dispatch()
+    };
+
+    (response.status(), response.into_json())
+}
+
+#[test]
+fn refused_votes_say_why() {
+    let dir = TestDir::new(&["03-10"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 10, 8, 0)));
+    let client = dir.client(&clock);
+
+    let refusal = |uri: String| {
+        let response = client.post(uri).dispatch();
+        (response.status(), response.into_json::<Value>().unwrap())
+    };
+
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+    assert_eq!(refusal(format!("/api/rtv/increment_blue/{}", voter_id("alice"))), (Status::Conflict, json!({ "error": "already_voted", "message": "You already voted today" })));
+    assert_eq!(refusal("/api/rtv/increment_red/not-a-token".to_string()), (Status::BadRequest, json!({ "error": "invalid_voter_id", "message": "Invalid voter id" })));
+    assert_results(&latest(&client), "03-10", 1, 0);
+
+    clock.advance(Duration::days(1));
+    assert_eq!(refusal(format!("/api/rtv/increment_red/{}", voter_id("alice"))), (Status::NotFound, json!({ "error": "no_poll_today", "message": "No poll today" })));
+}
+
+#[test]
+fn malformed_voter_ids_are_rejected() {
+    let dir = TestDir::new(&["03-10"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 10, 8, 0)));
+    let client = dir.client(&clock);
+
+    let too_long = "a".repeat(10_000);
+
+    let invalid_voter_id = (Status::BadRequest, Some(json!({ "error": "invalid_voter_id", "message": "Invalid voter id" })));
+
+    for voter_uuid in ["%20", "alice", "0123456789abcdef", "0123456789abcdef0123456789abcdeg", too_long.as_str()] {
+        for uri in [
+            format!("/api/rtv/has_user_voted/{}", voter_uuid),
+            format!("/api/rtv/users/{}/history", voter_uuid),
+            format!("/api/rtv/users/{}/profile", voter_uuid)
+        ] {
+            let response = client.get(uri).dispatch();
+            assert_eq!((response.status(), response.into_json::<Value>()), invalid_voter_id);
+        }
+
+        let response = client.post(format!("/api/rtv/increment_red/{}", voter_uuid)).dispatch();
+        assert_eq!((response.status(), response.into_json::<Value>()), invalid_voter_id);
+    }
+
+    assert_results(&latest(&client), "03-10", 0, 0);
+
+    // The same token in upper case is the same voter.
+    assert_eq!(client.post("/api/rtv/increment_red/0123456789ABCDEF0123456789ABCDEF").dispatch().status(), Status::Ok);
+    assert_eq!(client.post("/api/rtv/increment_red/0123456789abcdef0123456789abcdef").dispatch().status(), Status::Conflict);
+}
+
+#[test]
+fn voter_ids_are_only_stored_hashed_per_poll() {
+    let dir = TestDir::new(&["03-10", "03-11"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 10, 8, 0)));
+    let client = dir.client(&clock);
+
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+    clock.advance(Duration::days(1));
+    assert_eq!(vote(&client, "alice", false), Status::Ok);
+    assert_eq!(vote(&client, "alice", false), Status::Conflict);
+    assert_eq!(has_user_voted(&client, "alice"), "true");
+
+    let db_conn = Connection::open(dir.path.join("wyr_persistent.db")).unwrap();
+    let stored_voter_ids = db_conn.prepare("SELECT voter_id FROM responders").unwrap()
+        .query_map([], |row| row.get(0)).unwrap()
+        .collect::<rusqlite::Result<Vec<String>>>().unwrap();
+
+    // Neither poll stores the voter id itself, and the two polls can't be joined on the hash.
+    assert_eq!(stored_voter_ids.len(), 2);
+    assert!(!stored_voter_ids.contains(&voter_id("alice")));
+    assert_ne!(stored_voter_ids[0], stored_voter_ids[1]);
+}
+
+#[test]
+fn voting_history_lists_closed_polls_page_by_page() {
+    let dir = TestDir::new(&["03-10", "03-11", "03-12", "03-13"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 10, 8, 0)));
+    let client = dir.client(&clock);
+
+    let history = |query: &str| {
+        let response = client.get(format!("/api/rtv/users/{}/history{}", voter_id("alice"), query)).dispatch();
+        (response.status(), response.into_json::<Value>())
+    };
+
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+    assert_eq!(vote(&client, "bob", false), Status::Ok);
+    assert_eq!(vote(&client, "carol", false), Status::Ok);
+    clock.advance(Duration::days(1));
+    assert_eq!(vote(&client, "bob", true), Status::Ok);
+    clock.advance(Duration::days(1));
+    assert_eq!(vote(&client, "alice", false), Status::Ok);
+    assert_eq!(vote(&client, "bob", false), Status::Ok);
+    clock.advance(Duration::days(1));
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+
+    let wyr = json!({ "title": "Would You Rather", "subtitle": null, "icon_id": null });
+
+    let (status, page) = history("");
+    assert_eq!(status, Status::Ok);
+    assert_eq!(page.unwrap(), json!({
+        "page": 1,
+        "per_page": 5,
+        "total": 2,
+        "votes": [
+            {
+                "poll_date": "2026-03-12", "red_prompt": "Red 03-12", "blue_prompt": "Blue 03-12", "question_type": "WYR", "question_type_info": wyr,
+                "picked": "blue", "red_count": 0, "blue_count": 2, "in_majority": true,
+                "options": [{ "text": "Red 03-12", "vote_count": 0 }, { "text": "Blue 03-12", "vote_count": 2 }], "picked_option": 1
+            },
+            {
+                "poll_date": "2026-03-10", "red_prompt": "Red 03-10", "blue_prompt": "Blue 03-10", "question_type": "WYR", "question_type_info": wyr,
+                "picked": "red", "red_count": 1, "blue_count": 2, "in_majority": false,
+                "options": [{ "text": "Red 03-10", "vote_count": 1 }, { "text": "Blue 03-10", "vote_count": 2 }], "picked_option": 0
+            }
+        ]
+    }));
+
+    let (_, page) = history("?page=2&per_page=1");
+    let page = page.unwrap();
+    assert_eq!(page["total"], 2);
+    assert_eq!(page["votes"].as_array().unwrap().len(), 1);
+    assert_eq!(page["votes"][0]["poll_date"], "2026-03-10");
+
+    assert_eq!(history("?page=3&per_page=1").1.unwrap()["votes"], json!([]));
+    assert_eq!(history("?page=0").0, Status::BadRequest);
+    assert_eq!(history("?per_page=21").0, Status::BadRequest);
+    assert_eq!(client.get("/api/rtv/users/alice/history").dispatch().status(), Status::BadRequest);
+}
+
+#[test]
+fn streaks_count_polls_and_come_back_with_the_vote() {
+    let dir = TestDir::new(&["03-09", "03-10", "03-11", "03-12", "03-14", "03-15"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 9, 8, 0)));
+    let client = dir.client(&clock);
+
+    let profile = || client.get(format!("/api/rtv/users/{}/profile", voter_id("alice"))).dispatch().into_json::<Value>().unwrap();
+
+    assert_eq!(profile(), json!({ "current_streak": 0, "longest_streak": 0, "total_votes": 0, "majority_percentage": null }));
+
+    for (day, votes) in [(9, vec![("alice", true), ("bob", false), ("carol", false)]), (10, vec![("alice", false)]), (11, vec![("bob", true)]), (12, vec![("alice", true)])] {
+        clock.set(eastern(2026, 3, day, 8, 0));
+
+        for (voter, red) in votes {
+            assert_eq!(vote(&client, voter, red), Status::Ok);
+        }
+    }
+
+    // No poll on 03-13, the streak from 03-12 carries over, and holds until alice votes today.
+    clock.set(eastern(2026, 3, 14, 8, 0));
+    assert_eq!(profile(), json!({ "current_streak": 1, "longest_streak": 2, "total_votes": 3, "majority_percentage": 67 }));
+
+    let response = client.post("/api/rtv/vote").json(&json!({ "voter_id": voter_id("alice"), "choice": "red" })).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.into_json::<Value>().unwrap(), json!({
+        "red_prompt": "Red 03-14", "blue_prompt": "Blue 03-14", "question_type": "WYR", "red_count": 1, "blue_count": 0,
+        "poll": {
+            "poll_date": "2026-03-14", "question_type": "WYR",
+            "question_type_info": { "title": "Would You Rather", "subtitle": null, "icon_id": null },
+            "options": [{ "text": "Red 03-14", "vote_count": 1 }, { "text": "Blue 03-14", "vote_count": 0 }]
+        },
+        "current_streak": 2, "grace_deadline": "2026-03-14T12:01:00Z"
+    }));
+    assert_eq!(profile(), json!({ "current_streak": 2, "longest_streak": 2, "total_votes": 4, "majority_percentage": 67 }));
+
+    // Missing a whole poll ends the streak.
+    clock.advance(Duration::days(1));
+    assert_results(&latest(&client), "03-15", 0, 0);
+    clock.advance(Duration::days(1));
+    assert_eq!(profile()["current_streak"], 0);
+    assert_eq!(client.get("/api/rtv/users/alice/profile").dispatch().status(), Status::BadRequest);
+}
+
+#[test]
+fn unified_vote_endpoint_counts_the_choice_and_returns_the_results() {
+    let dir = TestDir::new(&["03-14"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 14, 8, 0)));
+    let client = dir.client(&clock);
+
+    let post_vote = |body: Value| {
+        let response = client.post("/api/rtv/vote").json(&body).dispatch();
+        (response.status(), response.into_json::<Value>().unwrap())
+    };
+
+    let (status, body) = post_vote(json!({ "voter_id": voter_id("alice"), "choice": "blue" }));
+    assert_eq!(status, Status::Ok);
+    assert_eq!(body["red_prompt"], "Red 03-14");
+    assert_eq!((body["red_count"].as_u64(), body["blue_count"].as_u64()), (Some(0), Some(1)));
+    assert_eq!(body["current_streak"], 1);
+
+    let (status, body) = post_vote(json!({ "voter_id": voter_id("bob"), "choice": "red", "poll_date": "2026-03-14" }));
+    assert_eq!(status, Status::Ok);
+    assert_eq!((body["red_count"].as_u64(), body["blue_count"].as_u64()), (Some(1), Some(1)));
+
+    // The old per-side routes count through the same path, with the empty body older builds expect.
+    let response = client.post(format!("/api/rtv/increment_red/{}", voter_id("carol"))).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.into_string(), None);
+    assert_eq!(post_vote(json!({ "voter_id": voter_id("carol"), "choice": "blue" })).1["error"], "already_voted");
+    assert_results(&latest(&client), "03-14", 2, 1);
+
+    assert_eq!(post_vote(json!({ "voter_id": voter_id("dave"), "choice": "red", "poll_date": "2026-03-13" })).1["error"], "wrong_poll");
+    assert_eq!(post_vote(json!({ "voter_id": voter_id("dave"), "choice": "green" })), (Status::BadRequest, json!({ "error": "invalid_vote", "message": "Invalid vote" })));
+    assert_eq!(post_vote(json!({ "voter_id": "dave", "choice": "red" })).1["error"], "invalid_voter_id");
+    assert_results(&latest(&client), "03-14", 2, 1);
+}
+
+#[test]
+fn v2_polls_carry_their_id_totals_and_schedule() {
+    let dir = TestDir::new(&["03-09", "03-10", "03-11"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 9, 8, 0)));
+    let client = dir.client(&clock);
+
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+    clock.set(eastern(2026, 3, 10, 8, 0));
+
+    let response = client.post("/api/rtv/v2/vote").json(&json!({ "voter_id": voter_id("alice"), "option": 1 })).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body = response.into_json::<Value>().unwrap();
+    assert_eq!(body["poll"]["total_votes"], 1);
+    assert_eq!(body["current_streak"], 2);
+    assert_eq!(vote(&client, "bob", true), Status::Ok);
+
+    let latest_poll = v2_poll(&client, "latest");
+    let poll_id = latest_poll["poll_id"].as_i64().unwrap();
+    assert_eq!(latest_poll, json!({
+        "poll_id": poll_id, "poll_date": "2026-03-10", "question_type": "WYR",
+        "question_type_info": { "title": "Would You Rather", "subtitle": null, "icon_id": null },
+        "options": [{ "text": "Red 03-10", "vote_count": 1 }, { "text": "Blue 03-10", "vote_count": 1 }],
+        "total_votes": 2, "closed": false, "next_poll_opens_at": "2026-03-11T04:00:00Z"
+    }));
+
+    let previous_poll = v2_poll(&client, "previous");
+    assert_eq!((previous_poll["poll_date"].as_str(), previous_poll["total_votes"].as_u64()), (Some("2026-03-09"), Some(1)));
+    assert_eq!(previous_poll["closed"], true);
+    assert!(previous_poll["poll_id"].as_i64().unwrap() < poll_id);
+    assert_eq!(v2_poll(&client, "2026-03-09"), previous_poll);
+
+    // Polls that haven't opened yet stay hidden, and the v1 routes keep their legacy shape.
+    assert_eq!(client.get("/api/rtv/v2/polls/2026-03-11").dispatch().status(), Status::NotFound);
+    assert_eq!(client.get("/api/rtv/v2/polls/03-09").dispatch().status(), Status::BadRequest);
+    assert_eq!(latest(&client), json!({
+        "red_prompt": "Red 03-10", "blue_prompt": "Blue 03-10", "question_type": "WYR", "red_count": 1, "blue_count": 1
+    }));
+
+    // A poll keeps its id once the next day starts.
+    clock.advance(Duration::days(1));
+    assert_eq!(v2_poll(&client, "previous")["poll_id"], poll_id);
+}
+
+#[test]
+fn v2_votes_can_name_their_poll_by_id() {
+    let dir = TestDir::new(&["03-13", "03-14"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 13, 23, 59)));
+    let client = dir.client(&clock);
+
+    let post_vote = |body: Value| {
+        let response = client.post("/api/rtv/v2/vote").json(&body).dispatch();
+        (response.status(), response.into_json::<Value>().unwrap())
+    };
+
+    let previous_id = v2_poll(&client, "latest")["poll_id"].as_i64().unwrap();
+    clock.set(eastern(2026, 3, 14, 0, 1));
+    let latest_id = v2_poll(&client, "latest")["poll_id"].as_i64().unwrap();
+
+    // Fetched at 23:59, sent at 00:01: counted on the poll it names, within the overlap.
+    let (status, body) = post_vote(json!({ "voter_id": voter_id("alice"), "choice": "red", "poll_id": previous_id }));
+    assert_eq!(status, Status::Ok);
+    assert_eq!(body["poll"]["poll_date"], "2026-03-13");
+
+    // The id has to be a poll's and agree with the poll_date if both are given.
+    assert_eq!(post_vote(json!({ "voter_id": voter_id("bob"), "choice": "red", "poll_id": latest_id, "poll_date": "2026-03-13" })).1["error"], "invalid_vote");
+    assert_eq!(post_vote(json!({ "voter_id": voter_id("bob"), "choice": "red", "poll_id": latest_id + 1 })).1["error"], "wrong_poll");
+    assert_eq!(post_vote(json!({ "voter_id": voter_id("bob"), "choice": "red", "poll_id": latest_id, "poll_date": "2026-03-14" })).0, Status::Ok);
+
+    clock.set(eastern(2026, 3, 14, 0, 2));
+    assert_eq!(post_vote(json!({ "voter_id": voter_id("carol"), "choice": "red", "poll_id": previous_id })).1["error"], "wrong_poll");
+
+    // The v1 route has no poll ids.
+    let response = client.post("/api/rtv/vote").json(&json!({ "voter_id": voter_id("carol"), "choice": "red", "poll_id": latest_id })).dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+
+    assert_results(&previous(&client), "03-13", 1, 0);
+    assert_results(&latest(&client), "03-14", 1, 0);
+}
+
+#[test]
+fn vote_cast_just_before_the_rollover_counts_on_the_poll_it_was_cast_for() {
+    let dir = TestDir::new(&["03-13", "03-14"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 13, 23, 59)));
+    let client = dir.client(&clock);
+
+    let post_vote = |voter: &str, poll_date: &str| {
+        let response = client.post("/api/rtv/vote").json(&json!({ "voter_id": voter_id(voter), "choice": "red", "poll_date": poll_date })).dispatch();
+        (response.status(), response.into_json::<Value>().unwrap())
+    };
+
+    assert_results(&latest(&client), "03-13", 0, 0);
+
+    // Fetched at 23:59, sent at 00:01: counted on 03-13, not on the prompt that just went live.
+    clock.set(eastern(2026, 3, 14, 0, 1));
+    let (status, body) = post_vote("alice", "2026-03-13");
+    assert_eq!(status, Status::Ok);
+    assert_results(&body, "03-13", 1, 0);
+    assert_eq!(body["grace_deadline"], "2026-03-14T04:01:00Z");
+    assert_results(&latest(&client), "03-14", 0, 0);
+    assert_eq!(has_user_voted(&client, "alice"), "false");
+
+    // The same voter can't slip a second vote into the previous poll, but still gets to vote on today's.
+    assert_eq!(post_vote("alice", "2026-03-13").1["error"], "already_voted");
+    assert_eq!(post_vote("alice", "2026-03-14").0, Status::Ok);
+
+    // Once the overlap is over, the previous poll is closed for good.
+    clock.set(eastern(2026, 3, 14, 0, 2));
+    assert_eq!(post_vote("bob", "2026-03-13"), (Status::Conflict, json!({ "error": "wrong_poll", "message": "That poll isn't open for votes" })));
+    assert_eq!(post_vote("bob", "2026-03-15").1["error"], "wrong_poll");
+
+    assert_results(&previous(&client), "03-13", 1, 0);
+    assert_results(&latest(&client), "03-14", 1, 0);
+}
+
+#[test]
+fn polls_with_more_than_two_options_are_voted_on_by_index() {
+    let dir = TestDir::new(&["03-10"]);
+    fs::write(
+        dir.path.join("prompts.toml"),
+        "[[prompts]]\ndate = \"03-11\"\noptions = [\"Rock\", \"Paper\", \"Scissors\"]\nquestion_type = \"TOT\"\n\n".to_string()
+            + &fs::read_to_string(dir.path.join("prompts.toml")).unwrap()
+    ).unwrap();
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 10, 8, 0)));
+    let client = dir.client(&clock);
+
+    let post_vote = |body: Value| {
+        let response = client.post("/api/rtv/vote").json(&body).dispatch();
+        (response.status(), response.into_json::<Value>().unwrap())
+    };
+    let counts = |poll: &Value| poll["options"].as_array().unwrap().iter().map(|option| option["vote_count"].as_u64().unwrap()).collect::<Vec<_>>();
+
+    // Two-option polls read the same through both views.
+    assert_eq!(vote(&client, "alice", false), Status::Ok);
+    let poll = client.get("/api/rtv/latest_poll").dispatch().into_json::<Value>().unwrap();
+    assert_eq!(poll["options"], json!([{ "text": "Red 03-10", "vote_count": 0 }, { "text": "Blue 03-10", "vote_count": 1 }]));
+
+    clock.advance(Duration::days(1));
+
+    let poll = client.get("/api/rtv/latest_poll").dispatch().into_json::<Value>().unwrap();
+    assert_eq!(poll["poll_date"], "2026-03-11");
+    assert_eq!(poll["options"], json!([
+        { "text": "Rock", "vote_count": 0 }, { "text": "Paper", "vote_count": 0 }, { "text": "Scissors", "vote_count": 0 }
+    ]));
+    assert_eq!(client.get("/api/rtv/latest_question_and_results").dispatch().status(), Status::NotFound);
+    assert_results(&previous(&client), "03-10", 0, 1);
+
+    // Red and blue only exist on two-option polls.
+    let response = client.post(format!("/api/rtv/increment_red/{}", voter_id("alice"))).dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    assert_eq!(response.into_json::<Value>().unwrap()["error"], "not_two_options");
+
+    let (status, body) = post_vote(json!({ "voter_id": voter_id("alice"), "option": 2 }));
+    assert_eq!(status, Status::Ok);
+    assert_eq!(counts(&body["poll"]), [0, 0, 1]);
+    assert!(body.get("red_prompt").is_none());
+
+    assert_eq!(post_vote(json!({ "voter_id": voter_id("bob"), "option": 3 })).1["error"], "invalid_option");
+    assert_eq!(post_vote(json!({ "voter_id": voter_id("bob"), "option": 1, "choice": "red" })).1["error"], "invalid_vote");
+    assert_eq!(post_vote(json!({ "voter_id": voter_id("bob"), "option": 1 })).0, Status::Ok);
+
+    // Switching on a poll without sides needs the option to switch to.
+    let switch = |uri: &str| client.put(uri).dispatch().into_json::<Value>().unwrap();
+    assert_eq!(switch(&format!("/api/rtv/vote/{}/switch", voter_id("alice")))["error"], "not_two_options");
+    assert_eq!(counts(&switch(&format!("/api/rtv/vote/{}/switch?option=1", voter_id("alice")))["poll"]), [0, 2, 0]);
+
+    clock.advance(Duration::days(1));
+
+    let history = client.get(format!("/api/rtv/users/{}/history", voter_id("alice"))).dispatch().into_json::<Value>().unwrap();
+    assert_eq!(history["votes"][0]["picked_option"], 1);
+    assert_eq!(history["votes"][0]["in_majority"], true);
+    assert!(history["votes"][0].get("picked").is_none());
+    assert_eq!(history["votes"][1]["picked"], "blue");
+}
+
+#[test]
+fn vote_can_be_switched_within_the_grace_period() {
+    let dir = TestDir::new(&["03-10"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 10, 8, 0)));
+    let client = dir.client(&clock);
+
+    let switch = |voter: &str| {
+        let response = client.put(format!("/api/rtv/vote/{}/switch", voter_id(voter))).dispatch();
+        (response.status(), response.into_json::<Value>().unwrap())
+    };
+
+    assert_eq!(switch("alice").1["error"], "not_voted");
+
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+    assert_eq!(vote(&client, "bob", true), Status::Ok);
+
+    // Switching twice goes back to red, the deadline stays the one of the original vote.
+    clock.advance(Duration::seconds(30));
+    let (status, body) = switch("alice");
+    assert_eq!(status, Status::Ok);
+    assert_eq!(body["grace_deadline"], "2026-03-10T12:01:00Z");
+    assert_results(&latest(&client), "03-10", 1, 1);
+    assert_eq!(switch("alice").0, Status::Ok);
+    assert_results(&latest(&client), "03-10", 2, 0);
+    assert_eq!(switch("alice").0, Status::Ok);
+
+    clock.advance(Duration::seconds(31));
+    assert_eq!(switch("alice"), (Status::Conflict, json!({ "error": "grace_period_over", "message": "Too late to change your vote" })));
+    assert_eq!(switch("bob").0, Status::Conflict);
+    assert_results(&latest(&client), "03-10", 1, 1);
+
+    clock.advance(Duration::days(1));
+    let history = client.get(format!("/api/rtv/users/{}/history", voter_id("alice"))).dispatch().into_json::<Value>().unwrap();
+    assert_eq!(history["votes"][0]["picked"], "blue");
+}
+
+#[test]
+fn retracted_vote_is_uncounted_and_audited() {
+    let dir = TestDir::new(&["03-10"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 10, 8, 0)));
+    let client = dir.client(&clock);
+
+    let retract = |voter: &str| client.delete(format!("/api/rtv/vote/{}", voter_id(voter))).dispatch().status();
+
+    assert_eq!(retract("alice"), Status::NotFound);
+
+    assert_eq!(vote(&client, "alice", false), Status::Ok);
+    assert_eq!(vote(&client, "bob", false), Status::Ok);
+    assert_eq!(retract("alice"), Status::NoContent);
+    assert_eq!(retract("alice"), Status::NotFound);
+
+    assert_results(&latest(&client), "03-10", 0, 1);
+    assert_eq!(has_user_voted(&client, "alice"), "false");
+
+    // Retracting frees the voter to vote again.
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+    assert_results(&latest(&client), "03-10", 1, 1);
+
+    let db_conn = Connection::open(dir.path.join("wyr_persistent.db")).unwrap();
+    let audit_entry: (String, String, usize) = db_conn
+        .query_row("SELECT poll_date, action, option_index FROM vote_audit", [], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap();
+    assert_eq!(audit_entry, ("2026-03-10".to_string(), "retract".to_string(), 1));
+}
+
+#[test]
+fn vote_cast_again_after_a_retraction_keeps_the_grace_deadline() {
+    let dir = TestDir::new(&["03-10"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 10, 8, 0)));
+    let client = dir.client(&clock);
+
+    let retract = |voter: &str| client.delete(format!("/api/rtv/vote/{}", voter_id(voter))).dispatch();
+    let vote_for = |voter: &str, choice: &str| {
+        let response = client.post("/api/rtv/vote").json(&json!({ "voter_id": voter_id(voter), "choice": choice })).dispatch();
+        (response.status(), response.into_json::<Value>().unwrap())
+    };
+
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+    assert_eq!(vote(&client, "bob", true), Status::Ok);
+
+    // Voting again doesn't restart the grace period, so retracting and voting again can't be used to switch late.
+    clock.advance(Duration::seconds(40));
+    assert_eq!(retract("alice").status(), Status::NoContent);
+    let (status, body) = vote_for("alice", "blue");
+    assert_eq!(status, Status::Ok);
+    assert_eq!(body["grace_deadline"], "2026-03-10T12:01:00Z");
+
+    clock.advance(Duration::seconds(21));
+    let response = retract("alice");
+    assert_eq!(response.status(), Status::Conflict);
+    assert_eq!(response.into_json::<Value>().unwrap()["error"], "grace_period_over");
+    assert_eq!(retract("bob").status(), Status::Conflict);
+    assert_eq!(vote_for("alice", "red").1["error"], "already_voted");
+    assert_results(&latest(&client), "03-10", 1, 1);
+}
+
+#[test]
+fn storage_failure_is_reported_and_the_server_keeps_serving() {
+    let dir = TestDir::new(&["03-10"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 10, 8, 0)));
+    let client = dir.client(&clock);
+
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+
+    // Pull the responders table out from under the server through a second connection.
+    let saboteur_conn = Connection::open(dir.path.join("wyr_persistent.db")).unwrap();
+    saboteur_conn.execute_batch("ALTER TABLE responders RENAME TO responders_aside").unwrap();
+
+    assert_eq!(client.get(format!("/api/rtv/has_user_voted/{}", voter_id("alice"))).dispatch().status(), Status::InternalServerError);
+    let response = client.post(format!("/api/rtv/increment_red/{}", voter_id("bob"))).dispatch();
+    assert_eq!(response.status(), Status::InternalServerError);
+    assert_eq!(response.into_json::<Value>().unwrap()["error"], "storage_error");
+    assert_results(&latest(&client), "03-10", 1, 0);
+
+    saboteur_conn.execute_batch("ALTER TABLE responders_aside RENAME TO responders").unwrap();
+
+    assert_eq!(has_user_voted(&client, "alice"), "true");
+    assert_eq!(vote(&client, "bob", false), Status::Ok);
+    assert_results(&latest(&client), "03-10", 1, 1);
+}
+
+#[test]
+fn failed_increment_leaves_no_voter_record_behind() {
+    let dir = TestDir::new(&["03-10"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 10, 8, 0)));
+    let client = dir.client(&clock);
+
+    // Make the increment fail after the voter record has been written.
+    let saboteur_conn = Connection::open(dir.path.join("wyr_persistent.db")).unwrap();
+    saboteur_conn.execute_batch(
+        "CREATE TRIGGER fail_increment BEFORE UPDATE ON poll_options BEGIN SELECT RAISE(ABORT, 'disk on fire'); END"
+    ).unwrap();
+
+    assert_eq!(vote(&client, "alice", true), Status::InternalServerError);
+    assert_eq!(has_user_voted(&client, "alice"), "false");
+
+    saboteur_conn.execute_batch("DROP TRIGGER fail_increment").unwrap();
+
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+    assert_eq!(vote(&client, "alice", true), Status::Conflict);
+    assert_results(&latest(&client), "03-10", 1, 0);
+}
+
+#[test]
+fn concurrent_votes_and_reads_are_all_counted() {
+    let dir = TestDir::new(&["03-10"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 10, 8, 0)));
+    let rtv_config: RtvConfig = dir.figment().extract().unwrap();
+    let persistent_data = Arc::new(PersistentData::new(&rtv_config, clock).unwrap());
+
+    let voters: Vec<_> = (0..8).map(|thread| {
+        let persistent_data = persistent_data.clone();
+
+        thread::spawn(move || {
+            for voter in 0..25 {
+                let voter_id = VoterId::from_param(&voter_id(&format!("voter-{}-{}", thread, voter))).unwrap();
+                persistent_data.db_increment(&voter_id, Ballot::Index(thread % 2), None).unwrap();
+
+                let current_poll = persistent_data.has_a_new_day_begun().unwrap();
+                assert!(persistent_data.poll_results(current_poll.date).unwrap().is_some());
+            }
+        })
+    }).collect();
+
+    for voter in voters {
+        voter.join().unwrap();
+    }
+
+    let current_poll = persistent_data.has_a_new_day_begun().unwrap();
+    let counts: Vec<u64> = persistent_data.poll_results(current_poll.date).unwrap().unwrap().options.iter().map(|option| option.vote_count).collect();
+    assert_eq!(counts, [100, 100]);
+}
+
+#[test]
+fn admin_api_requires_the_admin_token() {
+    let dir = TestDir::new(&["03-10"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 10, 8, 0)));
+    let client = dir.client(&clock);
+
+    assert_eq!(client.get("/api/rtv/admin/prompts").dispatch().status(), Status::Unauthorized);
+    assert_eq!(client.get("/api/rtv/admin/prompts").header(Header::new("Authorization", "Bearer let-me-out")).dispatch().status(), Status::Unauthorized);
+    assert_eq!(admin_request(&client, "GET", "/api/rtv/admin/prompts", None).0, Status::Ok);
+}
+
+#[test]
+fn admin_prompts_are_scheduled_and_locked_once_they_run() {
+    let dir = TestDir::new(&["03-10"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 10, 8, 0)));
+    let client = dir.client(&clock);
+
+    // A prompt scheduled tomorrow can be edited freely until then.
+    let (status, prompt) = admin_request(&client, "POST", "/api/rtv/admin/prompts", Some(json!({
+        "red_prompt": "Pirates", "blue_prompt": "Ninjas", "question_type": "WWW", "scheduled_date": "2026-03-11"
+    })));
+    assert_eq!(status, Status::Created);
+    let id = prompt.unwrap()["id"].as_i64().unwrap();
+
+    let (status, prompt) = admin_request(&client, "PUT", &format!("/api/rtv/admin/prompts/{}", id), Some(json!({
+        "red_prompt": "Robots", "blue_prompt": "Ninjas", "question_type": "WWW", "scheduled_date": "2026-03-11"
+    })));
+    assert_eq!(status, Status::Ok);
+    assert_eq!(prompt.unwrap()["red_prompt"], "Robots");
+
+    // Past dates and dates taken by another prompt are refused.
+    let (status, _) = admin_request(&client, "PUT", &format!("/api/rtv/admin/prompts/{}/schedule", id), Some(json!({ "scheduled_date": "2026-03-09" })));
+    assert_eq!(status, Status::Conflict);
+    let (status, _) = admin_request(&client, "POST", "/api/rtv/admin/prompts", Some(json!({
+        "red_prompt": "Tea", "blue_prompt": "Coffee", "question_type": "TOT", "scheduled_date": "2026-03-11"
+    })));
+    assert_eq!(status, Status::Conflict);
+
+    clock.advance(Duration::days(1));
+
+    let results = latest(&client);
+    assert_eq!(results["red_prompt"], "Robots");
+    assert_eq!(results["question_type"], "WWW");
+
+    // Today's prompt can be changed until the first vote comes in.
+    let (status, _) = admin_request(&client, "PUT", &format!("/api/rtv/admin/prompts/{}", id), Some(json!({
+        "red_prompt": "Robots", "blue_prompt": "Dinosaurs", "question_type": "WWW", "scheduled_date": "2026-03-11"
+    })));
+    assert_eq!(status, Status::Ok);
+    assert_eq!(latest(&client)["blue_prompt"], "Dinosaurs");
+
+    // That includes how many options it has, up to four.
+    let four_options = json!({ "options": ["Robots", "Dinosaurs", "Pirates", "Ninjas"], "question_type": "WWW", "scheduled_date": "2026-03-11" });
+    let (status, prompt) = admin_request(&client, "PUT", &format!("/api/rtv/admin/prompts/{}", id), Some(four_options));
+    assert_eq!(status, Status::Ok);
+    assert!(prompt.unwrap().get("red_prompt").is_none());
+    let poll = client.get("/api/rtv/latest_poll").dispatch().into_json::<Value>().unwrap();
+    assert_eq!(poll["options"].as_array().unwrap().len(), 4);
+
+    let five_options = json!({ "options": ["Robots", "Dinosaurs", "Pirates", "Ninjas", "Aliens"], "question_type": "WWW", "scheduled_date": "2026-03-11" });
+    assert_eq!(admin_request(&client, "PUT", &format!("/api/rtv/admin/prompts/{}", id), Some(five_options)).0, Status::UnprocessableEntity);
+    let (status, _) = admin_request(&client, "PUT", &format!("/api/rtv/admin/prompts/{}", id), Some(json!({
+        "red_prompt": "Robots", "blue_prompt": "Dinosaurs", "question_type": "WWW", "scheduled_date": "2026-03-11"
+    })));
+    assert_eq!(status, Status::Ok);
+
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+
+    let (status, _) = admin_request(&client, "PUT", &format!("/api/rtv/admin/prompts/{}", id), Some(json!({
+        "red_prompt": "Robots", "blue_prompt": "Ninjas", "question_type": "WWW", "scheduled_date": "2026-03-11"
+    })));
+    assert_eq!(status, Status::Conflict);
+    assert_eq!(admin_request(&client, "DELETE", &format!("/api/rtv/admin/prompts/{}", id), None).0, Status::Conflict);
+
+    // Once it has run it's history.
+    clock.advance(Duration::days(1));
+
+    assert_eq!(admin_request(&client, "DELETE", &format!("/api/rtv/admin/prompts/{}", id), None).0, Status::Conflict);
+    assert_eq!(previous(&client)["red_prompt"], "Robots");
+}
+
+#[test]
+fn admin_prompt_fills_todays_gap_until_unscheduled() {
+    let dir = TestDir::new(&["03-10"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 11, 8, 0)));
+    let client = dir.client(&clock);
+
+    assert_eq!(vote(&client, "alice", true), Status::NotFound);
+
+    let (status, prompt) = admin_request(&client, "POST", "/api/rtv/admin/prompts", Some(json!({
+        "red_prompt": "Sunrise", "blue_prompt": "Sunset", "question_type": "TOT", "scheduled_date": "2026-03-11"
+    })));
+    assert_eq!(status, Status::Created);
+    let id = prompt.unwrap()["id"].as_i64().unwrap();
+    assert_eq!(latest(&client)["red_prompt"], "Sunrise");
+
+    let (status, prompt) = admin_request(&client, "PUT", &format!("/api/rtv/admin/prompts/{}/schedule", id), Some(json!({ "scheduled_date": null })));
+    assert_eq!(status, Status::Ok);
+    assert_eq!(prompt.unwrap()["scheduled_date"], Value::Null);
+    assert_eq!(client.get("/api/rtv/latest_question_and_results").dispatch().status(), Status::NotFound);
+    assert_eq!(vote(&client, "alice", true), Status::NotFound);
+
+    assert_eq!(admin_request(&client, "DELETE", &format!("/api/rtv/admin/prompts/{}", id), None).0, Status::NoContent);
+    assert_eq!(admin_request(&client, "GET", &format!("/api/rtv/admin/prompts/{}", id), None).0, Status::NotFound);
+}
+
+#[test]
+fn calendar_is_imported_into_the_database_once() {
+    let dir = TestDir::with_backlog(&["02-29", "03-09", "03-10", "03-11"], &["spare"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 10, 8, 0)));
+    let client = dir.client(&clock);
+
+    // Dated prompts land on their next occurrence within a year, Feb 29 has none until 2028.
+    let (_, prompts) = admin_request(&client, "GET", "/api/rtv/admin/prompts", None);
+    let scheduled: Vec<(Value, Value)> = prompts.unwrap().as_array().unwrap().iter()
+        .map(|prompt| (prompt["red_prompt"].clone(), prompt["scheduled_date"].clone()))
+        .collect();
+    assert_eq!(scheduled, vec![
+        (json!("Red 03-10"), json!("2026-03-10")),
+        (json!("Red 03-11"), json!("2026-03-11")),
+        (json!("Red 03-09"), json!("2027-03-09")),
+        (json!("Red spare"), Value::Null)
+    ]);
+
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+    drop(client);
+
+    // Days already scheduled keep the prompt they got, edits to the file don't change them.
+    fs::write(dir.path.join("prompts.toml"), "[[prompts]]\ndate = \"03-11\"\nred_prompt = \"Edited\"\nblue_prompt = \"Edited\"\nquestion_type = \"TOT\"\n").unwrap();
+    clock.advance(Duration::days(1));
+    let client = dir.client(&clock);
+
+    assert_results(&latest(&client), "03-11", 0, 0);
+    assert_results(&previous(&client), "03-10", 1, 0);
+    assert_eq!(admin_request(&client, "GET", "/api/rtv/admin/prompts", None).1.unwrap().as_array().unwrap().len(), 4);
+}
+
+#[test]
+fn calendar_repeats_every_year() {
+    let dir = TestDir::new(&["03-10", "03-11", "03-12"]);
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 10, 8, 0)));
+    let client = dir.client(&clock);
+
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+
+    // Every rollover schedules the calendar's prompt a year ahead, a day the admin API unscheduled stays a gap.
+    clock.advance(Duration::days(1));
+    assert_results(&latest(&client), "03-11", 0, 0);
+    let (_, prompts) = admin_request(&client, "GET", "/api/rtv/admin/prompts", None);
+    let next_year_id = prompts.unwrap().as_array().unwrap().iter()
+        .find(|prompt| prompt["scheduled_date"] == "2027-03-10")
+        .map(|prompt| prompt["id"].clone())
+        .unwrap();
+    let (status, _) = admin_request(&client, "PUT", &format!("/api/rtv/admin/prompts/{}/schedule", next_year_id), Some(json!({ "scheduled_date": null })));
+    assert_eq!(status, Status::Ok);
+
+    clock.advance(Duration::days(364));
+    assert_eq!(client.get("/api/rtv/latest_question_and_results").dispatch().status(), Status::NotFound);
+    drop(client);
+
+    // 366 days after the import the calendar runs again, days scheduled while the server was down included.
+    clock.advance(Duration::days(1));
+    let client = dir.client(&clock);
+    assert_results(&latest(&client), "03-11", 0, 0);
+    assert_eq!(vote(&client, "alice", true), Status::Ok);
+
+    clock.advance(Duration::days(1));
+    assert_results(&latest(&client), "03-12", 0, 0);
+    assert_results(&previous(&client), "03-11", 1, 0);
+
+    // And again the year after, across Feb 29.
+    clock.advance(Duration::days(366));
+    assert_results(&latest(&client), "03-12", 0, 0);
+}
+
+#[test]
+fn question_types_come_from_the_calendar_on_every_start() {
+    let dir = TestDir::new(&["03-10"]);
+    let calendar_path = dir.path.join("prompts.toml");
+    let calendar = fs::read_to_string(&calendar_path).unwrap();
+    fs::write(&calendar_path, calendar.clone()
+        + "[[prompts]]\ndate = \"03-11\"\nred_prompt = \"Pineapple on pizza\"\nblue_prompt = \"Never\"\nquestion_type = \"HOT\"\n\n"
+        + "[[question_types]]\ncode = \"HOT\"\ntitle = \"Hot Take\"\nicon_id = \"flame\"\n"
+    ).unwrap();
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 10, 8, 0)));
+    let client = dir.client(&clock);
+
+    assert_eq!(v2_poll(&client, "latest")["question_type_info"], json!({ "title": "Would You Rather", "subtitle": null, "icon_id": null }));
+
+    // Only known question types can be used, through the admin API too.
+    let (status, _) = admin_request(&client, "POST", "/api/rtv/admin/prompts", Some(json!({
+        "red_prompt": "Cats", "blue_prompt": "Dogs", "question_type": "PETS"
+    })));
+    assert_eq!(status, Status::UnprocessableEntity);
+    drop(client);
+
+    // Headings are picked up on restart, even though the calendar's prompts were imported already.
+    fs::write(&calendar_path, calendar
+        + "[[question_types]]\ncode = \"HOT\"\ntitle = \"Hot Take\"\nsubtitle = \"Agree or disagree?\"\nicon_id = \"flame\"\n\n"
+        + "[[question_types]]\ncode = \"WYR\"\ntitle = \"Would you rather...\"\n"
+    ).unwrap();
+    clock.advance(Duration::days(1));
+    let client = dir.client(&clock);
+
+    let poll = v2_poll(&client, "latest");
+    assert_eq!(poll["question_type"], "HOT");
+    assert_eq!(poll["question_type_info"], json!({ "title": "Hot Take", "subtitle": "Agree or disagree?", "icon_id": "flame" }));
+    assert_eq!(v2_poll(&client, "previous")["question_type_info"]["title"], "Would you rather...");
+
+    // A calendar using a question type it doesn't define is refused.
+    fs::write(&calendar_path, "[[prompts]]\ndate = \"03-12\"\nred_prompt = \"Cats\"\nblue_prompt = \"Dogs\"\nquestion_type = \"PETS\"\n").unwrap();
+    let rtv_config: RtvConfig = dir.figment().extract().unwrap();
+    let error = PersistentData::new(&rtv_config, clock).err().unwrap().to_string();
+    assert!(error.contains(":2: unknown question_type \"PETS\""), "{}", error);
+}
+
+#[test]
+fn day_of_month_database_is_migrated_without_losing_tallies() {
+    let dir = TestDir::new(&["03-09", "03-10"]);
+    let db_path = dir.path.join("wyr_persistent.db");
+
+    let legacy_conn = Connection::open(&db_path).unwrap();
+    legacy_conn.execute_batch(
+        "CREATE TABLE vote_count (question_number INT PRIMARY KEY, red_vote_count INT, blue_vote_count INT);
+        CREATE TABLE responders (voter_id CHARACTER(16) PRIMARY KEY, response BOOLEAN NOT NULL);
+        INSERT INTO vote_count VALUES (9, 3, 4), (10, 1, 2), (31, 7, 7);"
+    ).unwrap();
+    legacy_conn.execute("INSERT INTO responders VALUES (?1, 1)", [voter_id("alice")]).unwrap();
+    drop(legacy_conn);
+
+    let clock = Arc::new(FakeClock::new(eastern(2026, 3, 10, 8, 0)));
+    let client = dir.client(&clock);
+
+    assert_results(&latest(&client), "03-10", 1, 2);
+    assert_results(&previous(&client), "03-09", 3, 4);
+    assert_eq!(has_user_voted(&client, "alice"), "true");
+    assert_eq!(vote(&client, "alice", false), Status::Conflict);
+    drop(client);
+
+    // Day 31 can't be in February, it's the tally of Jan 31.
+    let migrated_conn = Connection::open(&db_path).unwrap();
+    let january_tally: Vec<u64> = migrated_conn
+        .prepare("SELECT vote_count FROM poll_options WHERE poll_date = '2026-01-31' ORDER BY option_index").unwrap()
+        .query_map([], |row| row.get(0)).unwrap()
+        .collect::<rusqlite::Result<_>>().unwrap();
+    assert_eq!(january_tally, [7, 7]);
+    assert_eq!(migrations::schema_version(&migrated_conn).unwrap(), 8);
+    let is_raw_voter_id_stored = migrated_conn.prepare("SELECT 1 FROM responders WHERE voter_id = ?1").unwrap().exists([voter_id("alice")]).unwrap();
+    assert!(!is_raw_voter_id_stored);
+    drop(migrated_conn);
+
+    // Running the migrations again on restart is a no-op.
+    let client = dir.client(&clock);
+    assert_results(&latest(&client), "03-10", 1, 2);
+}
+
+#[test]
+#[should_panic(expected = "schema version 99")]
+fn database_from_a_newer_build_is_refused() {
+    let dir = TestDir::new(&["03-10"]);
+
+    Connection::open(dir.path.join("wyr_persistent.db")).unwrap().pragma_update(None, "user_version", 99).unwrap();
+
+    dir.client(&Arc::new(FakeClock::new(eastern(2026, 3, 10, 8, 0))));
+}
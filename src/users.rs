@@ -0,0 +1,257 @@
+use rocket::{Route, State};
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::{Serialize, json::Json};
+
+use rusqlite::{Connection, OptionalExtension};
+
+use chrono::NaiveDate;
+
+use crate::{PersistentData, QuestionResultsJSONResponse, VoterRequestError, storage_error};
+use crate::question_type::{QuestionType, QuestionTypeInfo};
+use crate::poll::{self, OptionResults, PollJSONResponse};
+use crate::voter::{InvalidVoterId, VoterId};
+
+/// Votes per page of history when the watch doesn't ask for a page size.
+const DEFAULT_PER_PAGE: u32 = 5;
+
+/// Largest page of history the watch can ask for.
+const MAX_PER_PAGE: u32 = 20;
+
+/// Struct that represents one poll a user voted on, as shown in their history.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct HistoryEntry {
+    poll_date: NaiveDate,           // Date of the poll.
+    question_type: QuestionType,    // The poll's QuestionType.
+    question_type_info: QuestionTypeInfo,   // Heading the watch shows for the QuestionType.
+    options: Vec<OptionResults>,    // The poll's options, exactly as they were worded that day, with their final counts.
+    picked_option: usize,           // Index of the option the user voted for.
+    in_majority: bool,              // Whether the option the user picked got strictly more votes than every other one, false on a tie.
+    #[serde(flatten)]
+    sides: Option<HistorySides>     // Red and blue view of the vote on two-option polls.
+}
+
+/// Struct that represents a vote on a two-option poll as red and blue, the way history entries were laid out before polls had more options.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct HistorySides {
+    red_prompt: String,             // The poll's red prompt, exactly as it was worded that day.
+    blue_prompt: String,            // The poll's blue prompt, exactly as it was worded that day.
+    picked: &'static str,           // Side the user voted for, "red" or "blue".
+    red_count: u64,                 // Final red vote count of the poll.
+    blue_count: u64                 // Final blue vote count of the poll.
+}
+
+/// Struct that represents JSON payload sent to RTV Pebble client for one page of a user's voting history.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct HistoryJSONResponse {
+    page: u32,                      // Page returned, starting at 1.
+    per_page: u32,                  // Most votes a page holds.
+    total: usize,                   // Polls the user voted on in all, across every page.
+    votes: Vec<HistoryEntry>        // The page's votes, most recent poll first.
+}
+
+/// Struct that represents JSON payload sent to RTV Pebble client with a user's participation stats.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct VoterStats {
+    current_streak: u32,                // Polls in a row the user voted on, up to today's (or yesterday's if they haven't voted yet today).
+    longest_streak: u32,                // Most polls in a row the user ever voted on.
+    total_votes: u32,                   // Polls the user voted on in all, today's included.
+    majority_percentage: Option<u32>    // Share of the user's closed polls where their side won, rounded, None until one of them closes.
+}
+
+/// Struct that represents how a voter took part in one poll.
+struct Participation {
+    poll_date: NaiveDate,           // Date of the poll.
+    picked_option: Option<usize>,   // Index of the option the voter picked, None if they didn't vote on it.
+    counts: Vec<u64>                // Vote count of every option of the poll, only read for polls the voter voted on.
+}
+
+impl Participation {
+    /// Whether the option the voter picked has strictly more votes than every other option, None if they didn't vote.
+    fn in_majority(&self) -> Option<bool> {
+        self.picked_option.map(|picked_option| {
+            self.counts.iter().enumerate().all(|(option_index, count)| option_index == picked_option || self.counts[picked_option] > *count)
+        })
+    }
+}
+
+impl PersistentData {
+    /// Grab how the given voter took part in every poll that ran, oldest first, along with the date of today's poll.
+    ///
+    /// Voters are stored under a different hash on every poll, so each poll is looked up with that poll's hash of the voter,
+    /// a single primary key lookup. Counts are only read for the polls they voted on. Days without a poll aren't listed at all.
+    fn participation(&self, db_conn: &Connection, voter_id: &VoterId) -> rusqlite::Result<(NaiveDate, Vec<Participation>)> {
+        let today = self.has_a_new_day_begun()?.date;
+
+        let poll_dates = db_conn
+            .prepare("SELECT poll_date FROM polls WHERE prompt_id IS NOT NULL AND poll_date <= ?1 ORDER BY poll_date")?
+            .query_map([today], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<NaiveDate>>>()?;
+
+        let mut response_query_statement = db_conn.prepare("SELECT option_index FROM responders WHERE poll_date = ?1 AND voter_id = ?2")?;
+
+        let mut participation = Vec::with_capacity(poll_dates.len());
+
+        for poll_date in poll_dates {
+            let picked_option = response_query_statement
+                .query_row((poll_date, self.voter_id_hasher.hash(poll_date, voter_id.as_str())), |row| row.get(0))
+                .optional()?;
+
+            let counts = match picked_option {
+                Some(_) => poll::counts_of_poll(db_conn, poll_date)?,
+                None => Vec::new()
+            };
+
+            participation.push(Participation { poll_date, picked_option, counts });
+        }
+
+        Ok((today, participation))
+    }
+
+    /// Grab one page of the closed polls the given voter voted on, most recent first, along with how many there are in all.
+    fn voting_history(&self, voter_id: &VoterId, page: u32, per_page: u32) -> rusqlite::Result<(usize, Vec<HistoryEntry>)> {
+        let db_conn = self.pool.reader()?;
+
+        let (today, participation) = self.participation(&db_conn, voter_id)?;
+
+        let votes: Vec<Participation> = participation.into_iter()
+            .rev()
+            .filter(|poll| poll.poll_date < today && poll.picked_option.is_some())
+            .collect();
+
+        let mut page_votes = Vec::new();
+
+        for poll in votes.iter().skip((page as usize - 1) * per_page as usize).take(per_page as usize) {
+            let results = poll::results_of_poll(&db_conn, poll.poll_date)?.ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+            let picked_option = poll.picked_option.unwrap_or_default();
+
+            // Red is option 0 and blue option 1.
+            let sides = QuestionResultsJSONResponse::from_poll(&results).map(|sides| HistorySides {
+                red_prompt: sides.red_prompt,
+                blue_prompt: sides.blue_prompt,
+                picked: if picked_option == 0 { "red" } else { "blue" },
+                red_count: sides.red_count,
+                blue_count: sides.blue_count
+            });
+
+            let PollJSONResponse { poll_date, question_type, question_type_info, options } = results;
+
+            page_votes.push(HistoryEntry {
+                poll_date,
+                question_type,
+                question_type_info,
+                options,
+                picked_option,
+                in_majority: poll.in_majority() == Some(true),
+                sides
+            });
+        }
+
+        Ok((votes.len(), page_votes))
+    }
+
+    /// Work out the given voter's streaks, vote count and how often they sided with the majority.
+    ///
+    /// Streaks count polls, not days, so a day without a poll doesn't break one. Today's poll only
+    /// extends the current streak once the voter votes on it, until then the streak still runs up to yesterday's.
+    pub fn voter_stats(&self, voter_id: &VoterId) -> rusqlite::Result<VoterStats> {
+        let (today, participation) = self.participation(&*self.pool.reader()?, voter_id)?;
+
+        let mut current_streak = 0;
+        let mut longest_streak = 0;
+
+        for poll in &participation {
+            current_streak = if poll.picked_option.is_some() { current_streak + 1 } else if poll.poll_date == today { current_streak } else { 0 };
+            longest_streak = longest_streak.max(current_streak);
+        }
+
+        let total_votes = participation.iter().filter(|poll| poll.picked_option.is_some()).count() as u32;
+
+        let closed_votes: Vec<bool> = participation.iter()
+            .filter(|poll| poll.poll_date < today)
+            .filter_map(Participation::in_majority)
+            .collect();
+        let majority_votes = closed_votes.iter().filter(|in_majority| **in_majority).count() as u32;
+        let majority_percentage = match closed_votes.len() as u32 {
+            0 => None,
+            closed_count => Some((majority_votes * 100 + closed_count / 2) / closed_count)
+        };
+
+        Ok(VoterStats { current_streak, longest_streak, total_votes, majority_percentage })
+    }
+
+    /// Work out the given voter's current streak alone, the way voter_stats does, cheaply enough to send back with every vote.
+    ///
+    /// Polls are looked up from today's backwards and the lookup stops at the first poll the voter skipped,
+    /// so it costs one primary key lookup per poll of the streak instead of one per poll that ever ran.
+    pub fn current_streak(&self, voter_id: &VoterId) -> rusqlite::Result<u32> {
+        let today = self.has_a_new_day_begun()?.date;
+
+        let db_conn = self.pool.reader()?;
+        let mut poll_dates_query_statement = db_conn.prepare("SELECT poll_date FROM polls WHERE prompt_id IS NOT NULL AND poll_date <= ?1 ORDER BY poll_date DESC")?;
+        let mut response_query_statement = db_conn.prepare("SELECT 1 FROM responders WHERE poll_date = ?1 AND voter_id = ?2")?;
+
+        let mut current_streak = 0;
+
+        for poll_date in poll_dates_query_statement.query_map([today], |row| row.get::<_, NaiveDate>(0))? {
+            let poll_date = poll_date?;
+
+            if response_query_statement.exists((poll_date, self.voter_id_hasher.hash(poll_date, voter_id.as_str())))? {
+                current_streak += 1;
+            } else if poll_date != today {
+                break;
+            }
+        }
+
+        Ok(current_streak)
+    }
+}
+
+/// API endpoint for GET-ing a page of the polls a user voted on, with the side they picked and whether it won.
+///
+/// Only closed polls are listed, today's vote shows up once the day rolls over. Pages start at 1 and hold
+/// DEFAULT_PER_PAGE votes unless per_page (up to MAX_PER_PAGE) says otherwise, a page past the end is empty.
+/// Responds 400 with the invalid_voter_id JSON error if the voter id isn't in the format VoterId expects,
+/// and 400 if the paging parameters are invalid.
+#[get("/<voter_uuid>/history?<page>&<per_page>")]
+fn get_history(
+    persistent_data: &State<PersistentData>,
+    voter_uuid: Result<VoterId, InvalidVoterId>,
+    page: Option<u32>,
+    per_page: Option<u32>
+) -> Result<Json<HistoryJSONResponse>, VoterRequestError> {
+    let voter_id = voter_uuid?;
+
+    let page = page.unwrap_or(1);
+    let per_page = per_page.unwrap_or(DEFAULT_PER_PAGE);
+
+    if page == 0 || per_page == 0 || per_page > MAX_PER_PAGE {
+        return Err(status::Custom(Status::BadRequest, "page must be at least 1 and per_page between 1 and 20").into());
+    }
+
+    let (total, votes) = persistent_data.voting_history(&voter_id, page, per_page)
+        .map_err(|e| storage_error(e, "could not read the voting history"))?;
+
+    Ok(Json(HistoryJSONResponse { page, per_page, total, votes }))
+}
+
+/// API endpoint for GET-ing a user's streaks, vote count and how often they sided with the majority.
+///
+/// Responds 400 with the invalid_voter_id JSON error if the voter id isn't in the format VoterId expects.
+#[get("/<voter_uuid>/profile")]
+fn get_profile(persistent_data: &State<PersistentData>, voter_uuid: Result<VoterId, InvalidVoterId>) -> Result<Json<VoterStats>, VoterRequestError> {
+    let voter_id = voter_uuid?;
+
+    persistent_data.voter_stats(&voter_id)
+        .map(Json)
+        .map_err(|e| storage_error(e, "could not read the profile").into())
+}
+
+/// Every user endpoint, mounted at /api/rtv/users/.
+pub fn routes() -> Vec<Route> {
+    routes![get_history, get_profile]
+}
@@ -0,0 +1,152 @@
+use rocket::{Route, State};
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::{Serialize, json::{self, Json}};
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::{PersistentData, VoteError, VoteInput, storage_error};
+use crate::poll::{self, PollJSONResponse};
+use crate::voter::VoterId;
+
+/// Struct that represents JSON payload sent to RTV Pebble client by the v2 API: a poll of any number of options,
+/// its results and where it stands in the daily schedule.
+///
+/// The v1 routes keep their own payloads, so watches already in the wild never see this shape.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PollV2JSONResponse {
+    poll_id: i64,                   // Id of the poll, stable for as long as the poll exists.
+    #[serde(flatten)]
+    poll: PollJSONResponse,         // The poll's date, QuestionType, heading and options with their results.
+    total_votes: u64,               // Votes across every option.
+    closed: bool,                   // Whether the next poll has opened, votes cast for this one are then only counted within the overlap.
+    next_poll_opens_at: DateTime<Utc>   // Instant this poll closes and the next one opens.
+}
+
+/// Struct that represents JSON payload sent to RTV Pebble client by the v2 API once a vote is counted.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct VoteV2JSONResponse {
+    poll: PollV2JSONResponse,       // The poll voted on, with this vote counted.
+    current_streak: u32,            // Polls in a row the voter voted on, with this vote counted.
+    grace_deadline: DateTime<Utc>   // Until when the vote can still be switched to another option.
+}
+
+impl PersistentData {
+    /// Grab the poll of the given date in its v2 shape, None if no poll ran that day or the day hasn't come yet.
+    ///
+    /// Polls scheduled for later days aren't shown, so the v2 API can't be used to read prompts ahead of time.
+    fn poll_v2(&self, poll_date: NaiveDate) -> rusqlite::Result<Option<PollV2JSONResponse>> {
+        let current_poll = self.has_a_new_day_begun()?;
+
+        if poll_date > current_poll.date {
+            return Ok(None);
+        }
+
+        let poll = match self.poll_results(poll_date)? {
+            Some(poll) => poll,
+            None => return Ok(None)
+        };
+
+        let poll_id = match poll::id_of_poll(&*self.pool.reader()?, poll_date)? {
+            Some(poll_id) => poll_id,
+            None => return Ok(None)
+        };
+
+        Ok(Some(PollV2JSONResponse {
+            poll_id,
+            total_votes: poll.options.iter().map(|option| option.vote_count).sum(),
+            closed: poll_date < current_poll.date,
+            next_poll_opens_at: self.poll_schedule.opens_at(poll_date.succ_opt().expect("poll date is before the last representable date")),
+            poll
+        }))
+    }
+
+    /// Date of the poll a vote was cast for, going by its poll_id or else its poll_date, None if it names neither.
+    ///
+    /// A poll_id no poll has is refused like a vote for a poll that isn't open, and one that disagrees with the poll_date
+    /// as an invalid vote. Whether the poll is still open for votes is left to db_increment.
+    fn poll_date_of_vote(&self, vote: &VoteInput) -> Result<Option<NaiveDate>, VoteError> {
+        let poll_id = match vote.poll_id {
+            Some(poll_id) => poll_id,
+            None => return Ok(vote.poll_date)
+        };
+
+        let poll_date = poll::date_of_poll(&*self.pool.reader()?, poll_id)?.ok_or(VoteError::WrongPoll)?;
+
+        if vote.poll_date.is_some_and(|vote_poll_date| vote_poll_date != poll_date) {
+            return Err(VoteError::InvalidVote);
+        }
+
+        Ok(Some(poll_date))
+    }
+}
+
+/// API endpoint for GET-ing current day's poll in its v2 shape.
+///
+/// Responds 404 "no poll today" if the calendar has no prompt today and the fallback policy doesn't provide one.
+#[get("/polls/latest")]
+fn get_latest_poll(persistent_data: &State<PersistentData>) -> Result<Json<PollV2JSONResponse>, status::Custom<&'static str>> {
+    let current_poll = persistent_data.has_a_new_day_begun().map_err(|e| storage_error(e, "could not read today's prompt"))?;
+
+    match persistent_data.poll_v2(current_poll.date) {
+        Ok(Some(poll)) => Ok(Json(poll)),
+        Ok(None) => Err(status::Custom(Status::NotFound, "no poll today")),
+        Err(e) => Err(storage_error(e, "could not read today's results"))
+    }
+}
+
+/// API endpoint for GET-ing previous day's poll in its v2 shape.
+///
+/// Responds 404 "no previous poll" if no poll ran the previous day.
+#[get("/polls/previous")]
+fn get_previous_poll(persistent_data: &State<PersistentData>) -> Result<Json<PollV2JSONResponse>, status::Custom<&'static str>> {
+    let current_poll = persistent_data.has_a_new_day_begun().map_err(|e| storage_error(e, "could not read the previous results"))?;
+
+    match persistent_data.poll_v2(current_poll.previous_date()) {
+        Ok(Some(poll)) => Ok(Json(poll)),
+        Ok(None) => Err(status::Custom(Status::NotFound, "no previous poll")),
+        Err(e) => Err(storage_error(e, "could not read the previous results"))
+    }
+}
+
+/// API endpoint for GET-ing the poll of a given date, as YYYY-MM-DD, in its v2 shape.
+///
+/// Responds 400 "invalid poll date" if the date can't be read, and 404 "no such poll" if no poll ran that day
+/// or the day hasn't come yet.
+#[get("/polls/<poll_date>")]
+fn get_poll(persistent_data: &State<PersistentData>, poll_date: &str) -> Result<Json<PollV2JSONResponse>, status::Custom<&'static str>> {
+    let poll_date = NaiveDate::parse_from_str(poll_date, "%Y-%m-%d").map_err(|_| status::Custom(Status::BadRequest, "invalid poll date"))?;
+
+    match persistent_data.poll_v2(poll_date) {
+        Ok(Some(poll)) => Ok(Json(poll)),
+        Ok(None) => Err(status::Custom(Status::NotFound, "no such poll")),
+        Err(e) => Err(storage_error(e, "could not read the poll"))
+    }
+}
+
+/// API endpoint for POST-ing a vote, with the same JSON body and errors as the v1 /vote, plus an optional poll_id.
+///
+/// The poll_id names the poll the vote was cast for, like poll_date does, so it goes to that poll as long as it's open for votes.
+/// Responds with the poll voted on in its v2 shape, the voter's current streak and until when the vote can be switched.
+#[post("/vote", data = "<vote>")]
+fn post_vote(persistent_data: &State<PersistentData>, vote: Result<Json<VoteInput>, json::Error<'_>>) -> Result<Json<VoteV2JSONResponse>, VoteError> {
+    let vote = vote.map_err(|_| VoteError::InvalidVote)?.into_inner();
+    let ballot = vote.ballot().ok_or(VoteError::InvalidVote)?;
+    let voter_id = VoterId::parse(&vote.voter_id)?;
+    let poll_date = persistent_data.poll_date_of_vote(&vote)?;
+
+    let (poll_date, grace_deadline) = persistent_data.db_increment(&voter_id, ballot, poll_date)?;
+
+    Ok(Json(VoteV2JSONResponse {
+        poll: persistent_data.poll_v2(poll_date)?.ok_or(VoteError::NoPollToday)?,
+        current_streak: persistent_data.current_streak(&voter_id)?,
+        grace_deadline
+    }))
+}
+
+/// Routes of the v2 API, mounted under /api/rtv/v2/.
+pub fn routes() -> Vec<Route> {
+    routes![get_latest_poll, get_previous_poll, get_poll, post_vote]
+}
@@ -0,0 +1,79 @@
+use rocket::request::FromParam;
+
+use chrono::NaiveDate;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Length of a voter id, in hexadecimal characters.
+const VOTER_ID_LENGTH: usize = 32;
+
+/// Shortest voter_id_secret accepted, in bytes.
+const MIN_SECRET_LENGTH: usize = 32;
+
+/// Struct that represents who is voting, as sent by the RTV Pebble client in the <voter_uuid> segment of the API's paths
+/// or the voter_id of a vote's body.
+///
+/// This is the one place the accepted format is defined: the Pebble account token, or the watch token when the phone
+/// isn't logged into a Pebble account, which are both 32 hexadecimal characters. Upper case digits are accepted and
+/// stored lower case, so the same token can't vote twice by changing case.
+///
+/// VoterId deliberately can't be written to the database, only its VoterIdHasher hash can.
+pub struct VoterId(String);
+
+impl VoterId {
+    /// Check that the given string is a voter id.
+    pub fn parse(voter_id: &str) -> Result<Self, InvalidVoterId> {
+        if voter_id.len() != VOTER_ID_LENGTH || !voter_id.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(InvalidVoterId);
+        }
+
+        Ok(VoterId(voter_id.to_ascii_lowercase()))
+    }
+
+    /// The voter id, lower case.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Error raised when a <voter_uuid> path segment isn't a valid voter id.
+#[derive(Debug)]
+pub struct InvalidVoterId;
+
+impl<'a> FromParam<'a> for VoterId {
+    type Error = InvalidVoterId;
+
+    fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+        VoterId::parse(param)
+    }
+}
+
+/// Struct that represents how voter ids are turned into what the database stores in their place.
+///
+/// A voter is stored as HMAC-SHA256(voter_id_secret, "<poll date>:<voter id>"), so the hash of the same voter changes
+/// from one poll to the next and a leaked database can neither be joined back to Pebble accounts nor across polls
+/// without the secret. Looking a voter up still works because the server gets the real voter id on every request.
+pub struct VoterIdHasher {
+    secret: Vec<u8>                 // voter_id_secret from the configuration.
+}
+
+impl VoterIdHasher {
+    /// Create a new VoterIdHasher, the secret has to be at least MIN_SECRET_LENGTH bytes long.
+    pub fn new(secret: &str) -> Result<Self, String> {
+        if secret.len() < MIN_SECRET_LENGTH {
+            return Err(format!("voter_id_secret must be at least {} characters long", MIN_SECRET_LENGTH));
+        }
+
+        Ok(VoterIdHasher { secret: secret.as_bytes().to_vec() })
+    }
+
+    /// Hash of the given voter on the poll of the given date, as hexadecimal.
+    ///
+    /// Takes the voter id as a plain string so ids stored before hashing can be migrated, requests go through VoterId::as_str().
+    pub fn hash(&self, poll_date: NaiveDate, voter_id: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC takes keys of any length");
+        mac.update(format!("{}:{}", poll_date, voter_id).as_bytes());
+
+        mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}